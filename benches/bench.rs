@@ -2,11 +2,42 @@ use std::ops::Range;
 
 use binggan::{BenchRunner, PeakMemAlloc, INSTRUMENTED_SYSTEM};
 use rand::{prelude::Distribution, rngs::StdRng, SeedableRng};
-use rice_coder::{create_rice_coder, estimate_optimal_k};
+use rice_coder::{
+    auto_block_encode, best_k_by_size, create_rice_coder, decode_docids, decode_pair_into,
+    encode_block_mode, encode_docids, estimate_optimal_k, reserve_for, ConstRiceCoder, RiceCoder,
+    SplitLayout,
+};
 
 #[global_allocator]
 pub static GLOBAL: &PeakMemAlloc<std::alloc::System> = &INSTRUMENTED_SYSTEM;
 
+fn encode_const<const K: u8>(data: &[u32]) -> Vec<u8> {
+    let mut encoded: Vec<u8> = Vec::with_capacity(data.len() * 4);
+    ConstRiceCoder::<K>::new().encode_vals(data, &mut encoded);
+    encoded
+}
+
+fn decode_const<const K: u8>(data: &[u8], num_vals: u32) -> Vec<u32> {
+    let mut decoded_values = Vec::new();
+    ConstRiceCoder::<K>::new().decode_into(data, &mut decoded_values, num_vals);
+    decoded_values
+}
+
+/// Dispatches a runtime `k` to the monomorphized `ConstRiceCoder` function, for every `k` up to
+/// `MAX_K`
+macro_rules! with_const_k {
+    ($k:expr, $func:ident $args:tt) => {
+        with_const_k!(@dispatch $k, $func $args,
+            0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31)
+    };
+    (@dispatch $k:expr, $func:ident $args:tt, $($const_k:literal)*) => {
+        match $k {
+            $($const_k => $func::<$const_k> $args,)*
+            k => unreachable!("k {} exceeds MAX_K", k),
+        }
+    };
+}
+
 fn bench_group() {
     // Tuples of name and data for the inputs
     let mut rng = StdRng::from_seed([3u8; 32]);
@@ -79,8 +110,33 @@ fn bench_group() {
 
                 Some(encoded.len() as u64)
             });
+            if *input_name == "random values u16::MAX" {
+                // Compare against `encode_vals`, which uses batched SIMD splitting with the
+                // `simd` feature
+                group.register_with_input(
+                    format!("write rice code per value k:{}", k),
+                    data,
+                    move |data| {
+                        let mut coder = create_rice_coder(k);
+                        let mut encoded: Vec<u8> = Vec::with_capacity(data.len() * 4);
+                        for value in data.iter() {
+                            coder.encode(*value, &mut encoded);
+                        }
+                        coder.finalize(&mut encoded);
+                        Some(encoded.len() as u64)
+                    },
+                );
+            }
+            group.register_with_input(
+                format!("write const rice code k:{}", k),
+                data,
+                move |data| {
+                    let encoded = with_const_k!(k, encode_const(data));
+                    Some(encoded.len() as u64)
+                },
+            );
         }
-        for percentile in [50, 80, 90, 100].iter() {
+        for percentile in [50.0, 80.0, 90.0, 100.0].iter() {
             group.register_with_input(
                 format!(
                     "write rice code k detect based on {} percentile",
@@ -103,6 +159,79 @@ fn bench_group() {
                 },
             );
         }
+        group.register_with_input("write rice code best k by size", data, move |data| {
+            let mut coder = create_rice_coder(best_k_by_size(data));
+            let mut encoded: Vec<u8> = Vec::with_capacity(data.len() * 4);
+            coder.encode_vals(data, &mut encoded);
+            Some(encoded.len() as u64)
+        });
+        // `encode_vals` reserves the output itself, compare against reserving it up front
+        group.register_with_input(
+            "write rice code best k by size without preallocation",
+            data,
+            move |data| {
+                let mut coder = create_rice_coder(best_k_by_size(data));
+                let mut encoded: Vec<u8> = Vec::new();
+                coder.encode_vals(data, &mut encoded);
+                Some(encoded.len() as u64)
+            },
+        );
+        group.register_with_input(
+            "write rice code best k by size preallocated with reserve_for",
+            data,
+            move |data| {
+                let k = best_k_by_size(data);
+                let mut coder = create_rice_coder(k);
+                let mut encoded: Vec<u8> = Vec::with_capacity(reserve_for(data, k));
+                coder.encode_vals(data, &mut encoded);
+                Some(encoded.len() as u64)
+            },
+        );
+        group.register_with_input("write delta rice code best k by size", data, move |data| {
+            let deltas: Vec<u32> = std::iter::once(data[0])
+                .chain(data.windows(2).map(|pair| pair[1] - pair[0]))
+                .collect();
+            let mut coder = create_rice_coder(best_k_by_size(&deltas));
+            let mut encoded: Vec<u8> = Vec::with_capacity(data.len() * 4);
+            coder.encode_deltas(data, &mut encoded);
+            Some(encoded.len() as u64)
+        });
+        group.register_with_input("write docids", data, move |data| {
+            Some(encode_docids(data).len() as u64)
+        });
+        // Falls back to bit-packing where Rice coding is larger, e.g. for uniform random values
+        group.register_with_input("write rice or fixed width block mode", data, move |data| {
+            let mut encoded: Vec<u8> = Vec::with_capacity(data.len() * 4);
+            encode_block_mode(data, &mut encoded);
+            Some(encoded.len() as u64)
+        });
+        if *input_name == "random values small range(0..5)" {
+            // The sorted values form a few long runs, compare against plain Rice coding above
+            group.register_with_input("write rle rice code best k by size", data, move |data| {
+                let mut coder = create_rice_coder(best_k_by_size(data));
+                let mut encoded: Vec<u8> = Vec::with_capacity(data.len() * 4);
+                coder.encode_rle(data, &mut encoded);
+                Some(encoded.len() as u64)
+            });
+        }
+        for block_size in [32, 64, 128] {
+            group.register_with_input(
+                format!("write adaptive rice code block size {}", block_size),
+                data,
+                move |data| {
+                    let mut coder = create_rice_coder(0);
+                    let mut encoded: Vec<u8> = Vec::with_capacity(data.len() * 4);
+                    coder.encode_adaptive(data, block_size, &mut encoded);
+                    Some(encoded.len() as u64)
+                },
+            );
+        }
+        // Block boundaries at distribution shifts instead of every `block_size` values
+        group.register_with_input("write auto block rice code", data, move |data| {
+            let mut encoded: Vec<u8> = Vec::with_capacity(data.len() * 4);
+            auto_block_encode(data, &mut encoded);
+            Some(encoded.len() as u64)
+        });
 
         group.run();
 
@@ -114,6 +243,24 @@ fn bench_group() {
             encoded_per_k[k as usize].0 = encoded;
             encoded_per_k[k as usize].1 = data.len() as u32;
         }
+        let split_per_k: Vec<(Vec<u8>, SplitLayout)> = (0..k_range.end)
+            .map(|k| {
+                let mut encoded: Vec<u8> = Vec::new();
+                let layout = create_rice_coder(k).encode_split(data, &mut encoded);
+                (encoded, layout)
+            })
+            .collect();
+        // Two columns with different k
+        let (k_a, k_b) = (k_range.start, k_range.end - 1);
+        let pair = (
+            encoded_per_k[k_a as usize].0.clone(),
+            encoded_per_k[k_b as usize].0.clone(),
+            data.len() as u32,
+        );
+        let docids = encode_docids(data);
+        let mut deltas = Vec::new();
+        create_rice_coder(k_a).encode_deltas(data, &mut deltas);
+        let deltas = (deltas, data.len() as u32);
         let mut group = runner.new_group();
         group.set_name(input_name);
         group.set_input_size(data.len() * std::mem::size_of::<u32>());
@@ -133,11 +280,114 @@ fn bench_group() {
                     Some(decoded_values.len() as u64)
                 },
             );
+            // Remainders unpacked independently of the quotients
+            group.register_with_input(
+                format!("read split rice code k:{}", k),
+                &split_per_k[k as usize],
+                move |(data, layout)| {
+                    let coder = create_rice_coder(k);
+                    let mut decoded_values = Vec::new();
+                    coder
+                        .decode_split_into(data, layout, &mut decoded_values)
+                        .unwrap();
+                    Some(decoded_values.len() as u64)
+                },
+            );
+            group.register_with_input(
+                format!("read const rice code k:{}", k),
+                encoded,
+                move |(data, num_vals)| {
+                    let decoded_values = with_const_k!(k, decode_const(data, *num_vals));
+                    Some(decoded_values.len() as u64)
+                },
+            );
         }
+        // Delta coded with the `k` picked for the deltas, against the fixed `k` of the range
+        group.register_with_input("read docids", &docids, move |docids| {
+            Some(decode_docids(docids).unwrap().len() as u64)
+        });
+        group.register_with_input(
+            format!("read delta rice code k:{}", k_a),
+            &deltas,
+            move |(data, num_vals)| {
+                let mut decoded_values = Vec::new();
+                create_rice_coder(k_a).decode_deltas_into(data, &mut decoded_values, *num_vals);
+                Some(decoded_values.len() as u64)
+            },
+        );
+        group.register_with_input(
+            format!("read two rice codes sequentially k:{},{}", k_a, k_b),
+            &pair,
+            move |(data_a, data_b, num_vals)| {
+                let (mut out_a, mut out_b) = (Vec::new(), Vec::new());
+                create_rice_coder(k_a).decode_into(data_a, &mut out_a, *num_vals);
+                create_rice_coder(k_b).decode_into(data_b, &mut out_b, *num_vals);
+                Some((out_a.len() + out_b.len()) as u64)
+            },
+        );
+        group.register_with_input(
+            format!("read two rice codes interleaved k:{},{}", k_a, k_b),
+            &pair,
+            move |(data_a, data_b, num_vals)| {
+                let (mut out_a, mut out_b) = (Vec::new(), Vec::new());
+                decode_pair_into(
+                    (data_a, k_a),
+                    (data_b, k_b),
+                    &mut out_a,
+                    &mut out_b,
+                    *num_vals,
+                );
+                Some((out_a.len() + out_b.len()) as u64)
+            },
+        );
         group.run();
     }
 }
 
+/// Encoding throughput on an input much larger than the caches
+fn bench_large_input() {
+    let mut rng = StdRng::from_seed([3u8; 32]);
+    let zipf = zipf::ZipfDistribution::new(200000, 1.5).unwrap();
+    let data: Vec<u32> = (0..10_000_000)
+        .map(|_docid| zipf.sample(&mut rng) as u32)
+        .collect();
+    let mut runner: BenchRunner = BenchRunner::new();
+    runner.set_alloc(GLOBAL);
+    let mut group = runner.new_group();
+    group.set_name("zipfs values max 200000, 10M values");
+    group.set_input_size(data.len() * std::mem::size_of::<u32>());
+    group.register_with_input("write rice code best k by size", &data, move |data| {
+        let mut coder = create_rice_coder(best_k_by_size(data));
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(data, &mut encoded);
+        Some(encoded.len() as u64)
+    });
+    group.register_with_input(
+        "write rice code best k by size preallocated with reserve_for",
+        &data,
+        move |data| {
+            let k = best_k_by_size(data);
+            let mut coder = create_rice_coder(k);
+            let mut encoded: Vec<u8> = Vec::with_capacity(reserve_for(data, k));
+            coder.encode_vals(data, &mut encoded);
+            Some(encoded.len() as u64)
+        },
+    );
+    // Copies the flushed bytes into the output in bulk, compare against the variants above
+    group.register_with_input(
+        "write rice code best k by size staged",
+        &data,
+        move |data| {
+            let mut coder = RiceCoder::with_staging(best_k_by_size(data));
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(data, &mut encoded);
+            Some(encoded.len() as u64)
+        },
+    );
+    group.run();
+}
+
 fn main() {
     bench_group();
+    bench_large_input();
 }
@@ -0,0 +1,28 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rice_coder::{decode_with_header, RiceCoder, RiceStreamDecoder};
+
+// The first byte selects `k` and whether escape codes are used, the rest is decoded. None of the
+// decoders may panic or loop forever on arbitrary input.
+fuzz_target!(|data: &[u8]| {
+    let Some((&params, input)) = data.split_first() else {
+        return;
+    };
+    let k = params & 0b1_1111;
+    let coder = if params & 0b10_0000 != 0 {
+        RiceCoder::with_escape(k, (params >> 6) as u32 * 8)
+    } else {
+        RiceCoder::new(k)
+    };
+
+    let mut out = Vec::new();
+    coder.decode_into(input, &mut out, u32::MAX);
+    let _ = coder.try_decode_into(input, &mut out, u32::MAX);
+    let _ = coder.decode_counted_into(input, &mut out);
+    let _ = coder.decode_adaptive(input, &mut out);
+    coder.decode_deltas_into(input, &mut out, u32::MAX);
+    coder.decode_into_result(input, &mut out, u32::MAX);
+    let _ = decode_with_header(input);
+    let _ = RiceStreamDecoder::new(input).decode_all();
+});
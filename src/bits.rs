@@ -0,0 +1,883 @@
+use alloc::vec::Vec;
+
+/// Order in which bits are packed into each byte.
+///
+/// The bit sequence itself is the same for both orders, e.g. the remainder is always written
+/// most significant bit first, only its placement within the bytes differs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BitOrder {
+    /// The first bit goes into the most significant bit of a byte
+    #[default]
+    MsbFirst,
+    /// The first bit goes into the least significant bit of a byte
+    LsbFirst,
+}
+
+impl BitOrder {
+    /// Converts bytes between MSB-first and this order, each byte of `word` independently
+    #[inline]
+    fn convert_u64(self, word: u64) -> u64 {
+        match self {
+            BitOrder::MsbFirst => word,
+            BitOrder::LsbFirst => word.reverse_bits().swap_bytes(),
+        }
+    }
+
+    #[inline]
+    fn convert_u32(self, word: u32) -> u32 {
+        match self {
+            BitOrder::MsbFirst => word,
+            BitOrder::LsbFirst => word.reverse_bits().swap_bytes(),
+        }
+    }
+
+    #[inline]
+    fn convert_u8(self, byte: u8) -> u8 {
+        match self {
+            BitOrder::MsbFirst => byte,
+            BitOrder::LsbFirst => byte.reverse_bits(),
+        }
+    }
+}
+
+/// Byte order of the 32-bit words a stream is stored in, applied after the bits are packed into
+/// bytes in the `BitOrder`.
+///
+/// Words are counted from the start of the stream. With `Big`, the bytes are stored in the order
+/// their bits complete, so `finalize` only pads to a whole byte. With `Little`, the bytes of each
+/// word are reversed, so `finalize` pads with `1`s to a whole word.
+///
+/// `BitOrder::MsbFirst` with `Little` matches formats reading the stream as little-endian `u32`
+/// words from the most significant bit down. Formats reading little-endian words from the least
+/// significant bit up already match `BitOrder::LsbFirst` with `Big`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ByteEndian {
+    /// The bytes of each word are stored in stream order
+    #[default]
+    Big,
+    /// The bytes of each word are stored in reverse stream order
+    Little,
+}
+
+impl ByteEndian {
+    /// Index of the stored byte holding byte `index` of the stream
+    #[inline]
+    fn physical_index(self, index: usize) -> usize {
+        match self {
+            ByteEndian::Big => index,
+            ByteEndian::Little => index ^ 3,
+        }
+    }
+
+    #[inline]
+    fn u32_bytes(self, word: u32) -> [u8; 4] {
+        match self {
+            ByteEndian::Big => word.to_be_bytes(),
+            ByteEndian::Little => word.to_le_bytes(),
+        }
+    }
+}
+
+/// Number of flushed bytes a staged `BitBuffer` collects before copying them into the output
+const STAGING_LEN: usize = 64;
+
+/// Flushed bytes not yet copied into the output
+#[derive(Debug, Clone, Copy)]
+struct Staging {
+    bytes: [u8; STAGING_LEN],
+    len: usize,
+}
+
+impl Default for Staging {
+    fn default() -> Self {
+        Staging {
+            bytes: [0; STAGING_LEN],
+            len: 0,
+        }
+    }
+}
+
+impl PartialEq for Staging {
+    /// Bytes past `len` were already copied into the output and don't count
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes[..self.len] == other.bytes[..other.len]
+    }
+}
+
+impl Eq for Staging {}
+
+/// Bit buffer shared by the coders, packing bits into bytes MSB-first by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BitBuffer {
+    buffer: u64,        // A 64-bit buffer to store bits before flushing
+    buffer_len: u8,     // Number of bits currently in the buffer
+    bytes_flushed: u64, // Number of bytes written to the output since the last finalize
+    order: BitOrder,
+    endian: ByteEndian,
+    staging: Option<Staging>, // Collects flushed bytes until finalize, if staged
+}
+
+impl Default for BitBuffer {
+    fn default() -> Self {
+        BitBuffer::new()
+    }
+}
+
+impl BitBuffer {
+    pub(crate) fn new() -> Self {
+        Self::with_order(BitOrder::MsbFirst)
+    }
+
+    pub(crate) fn with_order(order: BitOrder) -> Self {
+        Self::with_endian(order, ByteEndian::Big)
+    }
+
+    pub(crate) fn with_endian(order: BitOrder, endian: ByteEndian) -> Self {
+        BitBuffer {
+            buffer: 0,
+            buffer_len: 0,
+            bytes_flushed: 0,
+            order,
+            endian,
+            staging: None,
+        }
+    }
+
+    /// Collects flushed bytes in a staging array and copies them into the output in bulk,
+    /// whenever the array is full and on finalize. The output doesn't contain the flushed bytes
+    /// until then, so the same output has to be passed until finalize.
+    pub(crate) fn staged(self) -> Self {
+        BitBuffer {
+            staging: Some(Staging::default()),
+            ..self
+        }
+    }
+
+    pub(crate) fn is_staged(&self) -> bool {
+        self.staging.is_some()
+    }
+
+    /// Returns an empty buffer with the same settings, discarding buffered and staged bytes
+    pub(crate) fn cleared(&self) -> Self {
+        let bits = BitBuffer::with_endian(self.order, self.endian);
+        if self.is_staged() {
+            bits.staged()
+        } else {
+            bits
+        }
+    }
+
+    pub(crate) fn order(&self) -> BitOrder {
+        self.order
+    }
+
+    pub(crate) fn endian(&self) -> ByteEndian {
+        self.endian
+    }
+
+    /// Number of bits written since the last finalize, excluding padding
+    #[inline]
+    pub(crate) fn bits_written(&self) -> u64 {
+        self.bytes_flushed * 8 + self.buffer_len as u64
+    }
+
+    /// Helper function to flush the buffer to the output vector once it's full or when needed
+    ///
+    /// Flushes whole 32-bit words, or the full 64-bit buffer, leaving less than 32 bits in the
+    /// buffer. Each flush is a single `extend_from_slice` into the output, or a copy into the
+    /// staging array if staged.
+    #[inline]
+    pub(crate) fn flush(&mut self, output: &mut Vec<u8>) {
+        if self.buffer_len == 64 {
+            let word = self.order.convert_u64(self.buffer);
+            let bytes = match self.endian {
+                ByteEndian::Big => word.to_be_bytes(),
+                // Each 32-bit word reversed on its own
+                ByteEndian::Little => word.swap_bytes().rotate_left(32).to_be_bytes(),
+            };
+            self.emit(&bytes, output);
+            self.buffer = 0;
+            self.buffer_len = 0;
+        } else if self.buffer_len >= 32 {
+            let word = (self.buffer >> (self.buffer_len - 32)) as u32;
+            self.emit(&self.endian.u32_bytes(self.order.convert_u32(word)), output);
+            self.buffer_len -= 32;
+            self.buffer &= (1 << self.buffer_len) - 1; // Keep only remaining bits in buffer
+        }
+    }
+
+    /// Flushes all complete bytes with a single copy, leaving less than 8 bits in the buffer.
+    ///
+    /// Only for `ByteEndian::Big`, little-endian words can't be split.
+    fn flush_bytes(&mut self, output: &mut Vec<u8>) {
+        debug_assert_eq!(self.endian, ByteEndian::Big);
+        let num_bytes = (self.buffer_len / 8) as usize;
+        if num_bytes == 0 {
+            return;
+        }
+        // Left align the bits, so the complete bytes are the leading bytes
+        let aligned = self
+            .order
+            .convert_u64(self.buffer << (64 - self.buffer_len));
+        self.emit(&aligned.to_be_bytes()[..num_bytes], output);
+        self.buffer_len %= 8;
+        self.buffer &= (1 << self.buffer_len) - 1;
+    }
+
+    /// Appends flushed bytes to the output, or to the staging array if staged, copying the full
+    /// array into the output first if they don't fit
+    #[inline]
+    fn emit(&mut self, bytes: &[u8], output: &mut Vec<u8>) {
+        self.bytes_flushed += bytes.len() as u64;
+        match &mut self.staging {
+            None => output.extend_from_slice(bytes),
+            Some(staging) => {
+                if staging.len + bytes.len() > STAGING_LEN {
+                    output.extend_from_slice(&staging.bytes[..staging.len]);
+                    staging.len = 0;
+                }
+                staging.bytes[staging.len..][..bytes.len()].copy_from_slice(bytes);
+                staging.len += bytes.len();
+            }
+        }
+    }
+
+    /// Copies the staged bytes into the output
+    fn drain_staging(&mut self, output: &mut Vec<u8>) {
+        if let Some(staging) = &mut self.staging {
+            output.extend_from_slice(&staging.bytes[..staging.len]);
+            staging.len = 0;
+        }
+    }
+
+    /// Helper function to write bits to the buffer
+    ///
+    /// The caller has to make sure the bits fit, i.e. `buffer_len + num_bits <= 64`. After a
+    /// `flush` there is always room for 32 bits. Misuse is caught by debug assertions, since
+    /// the shift would silently drop bits.
+    #[inline]
+    pub(crate) fn write_bits(&mut self, value: u32, num_bits: u8) {
+        debug_assert!(num_bits <= 32, "at most 32 bits can be written at once");
+        debug_assert!(
+            self.buffer_len + num_bits <= 64,
+            "bit buffer overflow: {} + {} bits",
+            self.buffer_len,
+            num_bits
+        );
+        debug_assert!(
+            num_bits == 32 || value >> num_bits == 0,
+            "value {:#x} has more than {} bits",
+            value,
+            num_bits
+        );
+        self.buffer <<= num_bits;
+        self.buffer |= value as u64;
+        self.buffer_len += num_bits;
+    }
+
+    /// Writes `count` `1`s, e.g. the unary part of a value. Leaves less than 32 bits in the
+    /// buffer.
+    #[inline]
+    pub(crate) fn write_ones(&mut self, count: u32, output: &mut Vec<u8>) {
+        let mut remaining = count;
+
+        // Write blocks of 32 `1`s at a time
+        while remaining >= 32 {
+            self.write_bits(0xFFFFFFFF, 32); // 0xFFFFFFFF is thirty-two 1s
+            remaining -= 32;
+            self.flush(output);
+        }
+
+        // Write any remaining 1s
+        if remaining > 0 {
+            let mask = (1u32 << remaining) - 1; // Create a mask of `remaining` 1s
+            self.write_bits(mask, remaining as u8);
+            // Flush, so the terminating `0` and up to 32 more bits fit into the buffer.
+            self.flush(output);
+        }
+    }
+
+    /// Flushes any remaining bits in the buffer.
+    /// We will pad the remaining bits with `1`s to signal the end of the stream.
+    pub(crate) fn finalize(&mut self, output: &mut Vec<u8>) {
+        // Pad with 1s, so entry is invalid. On decompression this will be the
+        // EOF marker
+        if self.endian == ByteEndian::Little {
+            // Fill up the last word, which leaves no partial word to flush
+            self.flush(output);
+            let padding = 32 - self.buffer_len;
+            self.write_bits(u32::MAX >> (32 - padding), padding);
+            self.flush(output);
+        } else {
+            self.flush_bytes(output);
+            let padding = 8 - self.buffer_len;
+            self.write_bits((1 << padding) - 1, padding);
+            self.flush_bytes(output);
+        }
+        self.drain_staging(output);
+        self.bytes_flushed = 0;
+    }
+
+    /// Flushes any remaining bits in the buffer, padding the last partial byte, or word for
+    /// `ByteEndian::Little`, with `0`s.
+    ///
+    /// Returns the number of bits written since the last finalize, excluding the padding.
+    pub(crate) fn finalize_exact(&mut self, output: &mut Vec<u8>) -> u64 {
+        let num_bits = self.bits_written();
+        if self.endian == ByteEndian::Little {
+            self.flush(output);
+            if self.buffer_len > 0 {
+                self.write_bits(0, 32 - self.buffer_len);
+                self.flush(output);
+            }
+            self.drain_staging(output);
+            self.bytes_flushed = 0;
+            return num_bits;
+        }
+        self.flush_bytes(output);
+        if self.buffer_len > 0 {
+            let padding = 8 - self.buffer_len;
+            self.write_bits(0, padding);
+            self.flush_bytes(output);
+        }
+        self.drain_staging(output);
+        self.bytes_flushed = 0;
+        num_bits
+    }
+}
+
+/// Writes bits into an owned buffer, the primitives `RiceCoder` is built from, for composing
+/// custom codecs.
+///
+/// Bits are packed MSB-first, unless created with `BitWriter::with_order`. `finish` pads the
+/// last byte with `1`s like `RiceCoder::finalize`, so an unterminated unary run marks the end of
+/// the stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BitWriter {
+    bits: BitBuffer,
+    output: Vec<u8>,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_order(order: BitOrder) -> Self {
+        BitWriter {
+            bits: BitBuffer::with_order(order),
+            output: Vec::new(),
+        }
+    }
+
+    /// Writes `value` in unary: `value` `1`s terminated by a `0`
+    #[inline]
+    pub fn write_unary(&mut self, value: u32) {
+        self.bits.write_ones(value, &mut self.output);
+        self.bits.write_bits(0, 1);
+        self.bits.flush(&mut self.output);
+    }
+
+    /// Writes the lower `num_bits` bits of `value`, most significant bit first
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_bits > 32`.
+    #[inline]
+    pub fn write_bits(&mut self, value: u32, num_bits: u8) {
+        assert!(num_bits <= 32, "at most 32 bits can be written at once");
+        let value = if num_bits == 32 {
+            value
+        } else {
+            value & ((1 << num_bits) - 1)
+        };
+        self.bits.write_bits(value, num_bits);
+        self.bits.flush(&mut self.output);
+    }
+
+    /// Number of bits written so far, excluding padding
+    pub fn bits_written(&self) -> u64 {
+        self.bits.bits_written()
+    }
+
+    /// Pads the last byte with `1`s and returns the written bytes
+    pub fn finish(mut self) -> Vec<u8> {
+        self.bits.finalize(&mut self.output);
+        self.output
+    }
+}
+
+/// Reads bits from a byte slice, MSB-first by default, the counterpart of `BitBuffer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BitReader<'a> {
+    input: &'a [u8],
+    byte_pos: usize,
+    bit_pos: u8, // Next bit within `input[byte_pos]` in stream order, 0 is the first bit
+    order: BitOrder,
+    endian: ByteEndian,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(input: &'a [u8]) -> Self {
+        Self::with_order(input, BitOrder::MsbFirst)
+    }
+
+    pub(crate) fn with_order(input: &'a [u8], order: BitOrder) -> Self {
+        Self::with_endian(input, order, ByteEndian::Big)
+    }
+
+    pub(crate) fn with_endian(input: &'a [u8], order: BitOrder, endian: ByteEndian) -> Self {
+        BitReader {
+            input,
+            byte_pos: 0,
+            bit_pos: 0,
+            order,
+            endian,
+        }
+    }
+
+    /// Creates a reader starting at bit `bit_pos` of byte `byte_pos`, where `bit_pos` 0 is the
+    /// first bit of the byte in `order`. A `bit_pos` of 8 or more continues in the following
+    /// bytes.
+    pub(crate) fn with_position(
+        input: &'a [u8],
+        byte_pos: usize,
+        bit_pos: u8,
+        order: BitOrder,
+    ) -> Self {
+        BitReader {
+            input,
+            byte_pos: byte_pos.saturating_add((bit_pos / 8) as usize),
+            bit_pos: bit_pos % 8,
+            order,
+            endian: ByteEndian::Big,
+        }
+    }
+
+    /// Sets the byte order of the words, see `ByteEndian`
+    pub(crate) fn endian(mut self, endian: ByteEndian) -> Self {
+        self.endian = endian;
+        self
+    }
+
+    /// Returns the byte at `index` of the stream with its bits in MSB-first order
+    #[inline]
+    fn byte(&self, index: usize) -> Option<u8> {
+        self.input
+            .get(self.endian.physical_index(index))
+            .map(|byte| self.order.convert_u8(*byte))
+    }
+
+    /// Returns the bytes of the stream from `byte_pos` on, at most 8 and only as long as they are
+    /// contiguous, as an MSB-first word with the missing bytes zeroed, and their number.
+    #[inline]
+    fn load_word(&self) -> (u64, usize) {
+        let mut bytes = [0u8; 8];
+        let num_bytes = match self.endian {
+            ByteEndian::Big => {
+                let end = core::cmp::min(self.input.len(), self.byte_pos.saturating_add(8));
+                let available = self.input.get(self.byte_pos..end).unwrap_or_default();
+                bytes[..available.len()].copy_from_slice(available);
+                available.len()
+            }
+            ByteEndian::Little => {
+                let mut num_bytes = 0;
+                while num_bytes < 8 {
+                    let index = self.endian.physical_index(self.byte_pos + num_bytes);
+                    let Some(byte) = self.input.get(index) else {
+                        break;
+                    };
+                    bytes[num_bytes] = *byte;
+                    num_bytes += 1;
+                }
+                num_bytes
+            }
+        };
+        let word = self.order.convert_u64(u64::from_be_bytes(bytes));
+        (word, num_bytes)
+    }
+
+    /// Number of bits read so far
+    #[inline]
+    pub(crate) fn bits_read(&self) -> u64 {
+        self.byte_pos as u64 * 8 + self.bit_pos as u64
+    }
+
+    /// Index of the byte containing the next bit
+    #[inline]
+    pub(crate) fn byte_pos(&self) -> usize {
+        self.byte_pos
+    }
+
+    /// Position of the next bit within `input[byte_pos]`, 0 is the first bit
+    #[inline]
+    pub(crate) fn bit_pos(&self) -> u8 {
+        self.bit_pos
+    }
+
+    /// Number of bytes read so far, counting a partially read byte as read
+    #[inline]
+    pub(crate) fn bytes_touched(&self) -> usize {
+        self.byte_pos + (self.bit_pos > 0) as usize
+    }
+
+    /// Number of bytes up to the end of the byte containing the next bit, or of its word for
+    /// `ByteEndian::Little`. After the last value, this includes the padding written by
+    /// `BitBuffer::finalize`.
+    #[inline]
+    pub(crate) fn padding_end(&self) -> usize {
+        self.byte_pos.saturating_add(self.padding_len())
+    }
+
+    /// Like `padding_end`, but at most the input length
+    #[inline]
+    pub(crate) fn bytes_read(&self) -> usize {
+        core::cmp::min(self.padding_end(), self.input.len())
+    }
+
+    /// Number of bytes `BitBuffer::finalize` pads to, starting at `byte_pos`: the rest of the
+    /// byte, or of the word for `ByteEndian::Little`
+    #[inline]
+    fn padding_len(&self) -> usize {
+        match self.endian {
+            ByteEndian::Big => 1,
+            ByteEndian::Little => 4 - self.byte_pos % 4,
+        }
+    }
+
+    /// Returns true if the next `num_bytes` bytes, starting at the next bit, are all `1`s
+    fn ones_ahead(&self, num_bytes: usize) -> bool {
+        (0..num_bytes).all(|i| {
+            let mask = if i == 0 { 0xFFu8 >> self.bit_pos } else { 0xFF };
+            self.byte(self.byte_pos + i)
+                .is_some_and(|byte| byte & mask == mask)
+        })
+    }
+
+    /// Returns true if the remaining bits can only be the padding written by
+    /// `BitBuffer::finalize`: they are all `1`s within the last byte, or word for
+    /// `ByteEndian::Little`.
+    pub(crate) fn at_padding(&self) -> bool {
+        match self.input.len().checked_sub(self.byte_pos) {
+            None | Some(0) => true,
+            Some(remaining) if remaining <= self.padding_len() => self.ones_ahead(remaining),
+            Some(_) => false,
+        }
+    }
+
+    /// Returns true if the rest of the current byte, or word for `ByteEndian::Little`, consists
+    /// of the `1`s written by `finalize`. At a boundary `finalize` writes a full byte or word of
+    /// padding, which has to be present.
+    #[inline]
+    pub(crate) fn padding_valid(&self) -> bool {
+        self.ones_ahead(self.padding_len())
+    }
+
+    /// Returns the next 8 bits without advancing, or `None` if less than 8 bits are left
+    #[inline]
+    pub(crate) fn peek_byte(&self) -> Option<u8> {
+        let first = self.byte(self.byte_pos)?;
+        if self.bit_pos == 0 {
+            return Some(first);
+        }
+        let second = self.byte(self.byte_pos + 1)?;
+        Some((first << self.bit_pos) | (second >> (8 - self.bit_pos)))
+    }
+
+    /// Reads a single bit
+    #[inline]
+    pub(crate) fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.byte(self.byte_pos)?;
+        let bit = (byte >> (7 - self.bit_pos)) & 1 == 1;
+        self.advance(1);
+        Some(bit)
+    }
+
+    /// Reads `num_bits` bits, at most 32, as the low bits of a `u32`
+    #[inline]
+    pub(crate) fn read_bits(&mut self, num_bits: u8) -> Option<u32> {
+        let mut value = 0;
+        for _ in 0..num_bits {
+            value = (value << 1) | (self.read_bit()? as u32);
+        }
+        Some(value)
+    }
+
+    /// Reads a unary run of `1`s, up to `max` of them, and returns its length.
+    ///
+    /// The terminating `0` is consumed, unless the run is stopped by `max`. Returns `None` if the
+    /// input ends before the terminating `0` or `max` is reached. Since `finalize` pads with `1`s,
+    /// the padding can never be mistaken for a value.
+    /// Equivalent to calling `read_bit` until it doesn't return `Some(true)`, but counts up to 64
+    /// bits at once.
+    #[inline]
+    pub(crate) fn read_unary(&mut self, max: u32) -> Option<u32> {
+        let mut count: u32 = 0;
+        while count < max {
+            let (word, num_bytes) = self.load_word();
+            if num_bytes == 0 {
+                break;
+            }
+            let word = word << self.bit_pos;
+            let num_bits = num_bytes as u32 * 8 - self.bit_pos as u32;
+
+            let ones = core::cmp::min(word.leading_ones(), num_bits);
+            let take = core::cmp::min(ones, max - count);
+            count += take;
+            if ones < num_bits && count < max {
+                // Consume the terminating `0`
+                self.advance(take + 1);
+                return Some(count);
+            }
+            self.advance(take);
+        }
+        (count == max).then_some(count)
+    }
+
+    /// Reads up to `max_runs` unary runs that end within the next 8 bytes, passing their lengths
+    /// to `sink`, and returns the number of runs read. Reads nothing if less than 8 bytes are
+    /// left or the next run doesn't end within them.
+    ///
+    /// Decodes several values of a `k = 0` stream per word, counting each run with
+    /// `leading_ones`.
+    #[inline]
+    pub(crate) fn read_unary_runs(&mut self, max_runs: usize, mut sink: impl FnMut(u32)) -> usize {
+        let (word, num_bytes) = self.load_word();
+        if num_bytes < 8 {
+            return 0;
+        }
+        let mut word = word << self.bit_pos;
+        let num_bits = 64 - self.bit_pos as u32;
+        let mut used = 0;
+        let mut num_runs = 0;
+        while num_runs < max_runs {
+            let ones = word.leading_ones();
+            if used + ones >= num_bits {
+                break; // The terminating `0` is beyond the word
+            }
+            sink(ones);
+            num_runs += 1;
+            used += ones + 1;
+            word = word.checked_shl(ones + 1).unwrap_or(0);
+        }
+        self.advance(used);
+        num_runs
+    }
+
+    /// Skips `num_bits` bits
+    #[inline]
+    pub(crate) fn advance(&mut self, num_bits: u32) {
+        let total = self.bit_pos as u32 + num_bits;
+        self.byte_pos += (total / 8) as usize;
+        self.bit_pos = (total % 8) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_public_bit_writer() {
+        let mut writer = BitWriter::new();
+        writer.write_unary(3);
+        // Bits above `num_bits` are ignored
+        writer.write_bits(0b1111_0101, 4);
+        writer.write_bits(u32::MAX, 32);
+        writer.write_unary(0);
+        assert_eq!(writer.bits_written(), 4 + 4 + 32 + 1);
+        assert_eq!(
+            writer.finish(),
+            [0b1110_0101, 0xFF, 0xFF, 0xFF, 0xFF, 0b0111_1111]
+        );
+
+        // A `RiceCoder` code is the unary quotient followed by the remainder bits
+        let mut writer = BitWriter::new();
+        writer.write_unary(37 >> 3);
+        writer.write_bits(37, 3);
+        let mut expected = Vec::new();
+        crate::RiceCoder::new(3).encode_vals(&[37], &mut expected);
+        assert_eq!(writer.finish(), expected);
+
+        assert_eq!(BitWriter::new().finish(), [0xFF]);
+    }
+
+    #[test]
+    #[should_panic(expected = "at most 32 bits")]
+    fn test_public_bit_writer_too_many_bits() {
+        BitWriter::new().write_bits(0, 33);
+    }
+
+    proptest! {
+        #[test]
+        fn test_bit_writer_bytes(chunks in prop::collection::vec((any::<u32>(), 0u8..=32), 0..40)) {
+            let mut writer = BitBuffer::new();
+            let mut output = Vec::new();
+            let mut bits: Vec<bool> = Vec::new();
+            for (value, num_bits) in chunks {
+                let value = if num_bits == 32 { value } else { value & ((1 << num_bits) - 1) };
+                writer.write_bits(value, num_bits);
+                writer.flush(&mut output);
+                bits.extend((0..num_bits).rev().map(|bit| (value >> bit) & 1 == 1));
+            }
+            writer.finalize(&mut output);
+            bits.extend(std::iter::repeat_n(true, 8 - bits.len() % 8));
+            let expected: Vec<u8> = bits
+                .chunks(8)
+                .map(|byte| byte.iter().fold(0u8, |acc, bit| (acc << 1) | *bit as u8))
+                .collect();
+            prop_assert_eq!(output, expected);
+        }
+    }
+
+    /// Bit-by-bit reference for `read_unary`
+    fn read_unary_reference(reader: &mut BitReader, max: u32) -> Option<u32> {
+        let mut count = 0;
+        while count < max {
+            match reader.read_bit()? {
+                true => count += 1,
+                false => break,
+            }
+        }
+        Some(count)
+    }
+
+    #[test]
+    fn test_read_unary_runs() {
+        // 2, 0, 3, then a run of 64 ending beyond the word
+        let mut input = [0xFF; 10];
+        input[0] = 0b1100_1110;
+        input[9] = 0b0111_1111;
+        let mut reader = BitReader::new(&input);
+        let mut runs = Vec::new();
+        assert_eq!(reader.read_unary_runs(2, |run| runs.push(run)), 2);
+        assert_eq!(runs, [2, 0]);
+        assert_eq!(reader.read_unary_runs(8, |run| runs.push(run)), 1);
+        assert_eq!(runs, [2, 0, 3]);
+        assert_eq!((reader.byte_pos, reader.bit_pos), (1, 0));
+        assert_eq!(reader.read_unary_runs(8, |run| runs.push(run)), 0);
+        assert_eq!(reader.read_unary(u32::MAX), Some(64));
+        // Less than 8 bytes left
+        assert_eq!(reader.read_unary_runs(8, |run| runs.push(run)), 0);
+        assert_eq!(runs.len(), 3);
+    }
+
+    #[test]
+    fn test_read_bits() {
+        let mut reader = BitReader::new(&[0b1011_0011, 0b1000_0000]);
+        assert_eq!(reader.read_bit(), Some(true));
+        assert_eq!(reader.read_bits(3), Some(0b011));
+        assert_eq!(reader.read_bits(5), Some(0b0_0111));
+        assert_eq!((reader.byte_pos, reader.bit_pos), (1, 1));
+        assert_eq!(reader.bytes_touched(), 2);
+        assert_eq!(reader.read_bits(8), None);
+    }
+
+    #[test]
+    fn test_at_padding() {
+        assert!(BitReader::new(&[]).at_padding());
+        assert!(BitReader::new(&[0xFF]).at_padding());
+        assert!(!BitReader::new(&[0xFF, 0xFF]).at_padding());
+        let mut reader = BitReader::new(&[0b0001_0111]);
+        assert!(!reader.at_padding());
+        reader.advance(4);
+        assert!(!reader.at_padding());
+        reader.advance(1);
+        assert!(reader.at_padding());
+    }
+
+    #[test]
+    fn test_bit_buffer_fills_to_64_bits() {
+        let mut bits = BitBuffer::new();
+        bits.write_bits(u32::MAX, 32);
+        bits.write_bits(0, 31);
+        bits.write_bits(1, 1);
+        let mut output = Vec::new();
+        bits.flush(&mut output);
+        assert_eq!(output, [0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 1]);
+        assert_eq!(bits.bits_written(), 64);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "bit buffer overflow: 40 + 25 bits")]
+    fn test_bit_buffer_overflow() {
+        let mut bits = BitBuffer::new();
+        bits.write_bits(0, 20);
+        bits.write_bits(0, 20);
+        bits.write_bits(0, 25);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "at most 32 bits")]
+    fn test_bit_buffer_too_many_bits() {
+        BitBuffer::new().write_bits(0, 33);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "has more than 3 bits")]
+    fn test_bit_buffer_value_too_wide() {
+        BitBuffer::new().write_bits(0b1000, 3);
+    }
+
+    #[test]
+    fn test_padding_valid() {
+        assert!(!BitReader::new(&[]).padding_valid());
+        assert!(BitReader::new(&[0xFF]).padding_valid());
+        assert!(!BitReader::new(&[0xFE]).padding_valid());
+        let mut reader = BitReader::new(&[0b0101_1111, 0x00]);
+        reader.advance(4);
+        assert!(reader.padding_valid());
+        reader.advance(4);
+        assert!(!reader.padding_valid());
+        let reader = BitReader::with_order(&[0b1110_1010], BitOrder::LsbFirst);
+        assert!(!reader.padding_valid());
+    }
+
+    #[test]
+    fn test_peek_byte() {
+        let mut reader = BitReader::new(&[0b1011_0011, 0b1000_0001]);
+        assert_eq!(reader.peek_byte(), Some(0b1011_0011));
+        reader.advance(3);
+        assert_eq!(reader.peek_byte(), Some(0b1001_1100));
+        reader.advance(5);
+        assert_eq!(reader.peek_byte(), Some(0b1000_0001));
+        reader.advance(1);
+        assert_eq!(reader.peek_byte(), None);
+    }
+
+    #[test]
+    fn test_read_unary() {
+        let mut reader = BitReader::new(&[0b1110_1111, 0b1111_1111, 0b1011_1111]);
+        assert_eq!(reader.read_unary(u32::MAX), Some(3));
+        assert_eq!(reader.read_unary(u32::MAX), Some(13));
+        assert_eq!(reader.read_unary(2), Some(2));
+        // Run of `1`s reaching the end of the input
+        assert_eq!(reader.read_unary(u32::MAX), None);
+    }
+
+    proptest! {
+        #[test]
+        fn test_read_unary_matches_reference(
+            input in prop::collection::vec(prop_oneof![Just(0xFFu8), any::<u8>()], 0..24),
+            start_bit in 0u32..64,
+            max in prop_oneof![Just(u32::MAX), 0u32..80],
+        ) {
+            let mut reader = BitReader::new(&input);
+            reader.advance(core::cmp::min(start_bit, input.len() as u32 * 8));
+            let mut reference = reader;
+            loop {
+                let count = reader.read_unary(max);
+                let expected = read_unary_reference(&mut reference, max);
+                prop_assert_eq!(count, expected);
+                prop_assert_eq!(reader, reference);
+                if reader.byte_pos() >= input.len() || count.is_none_or(|count| count == max) {
+                    break;
+                }
+            }
+        }
+    }
+}
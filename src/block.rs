@@ -0,0 +1,119 @@
+use alloc::vec::Vec;
+
+use crate::{best_k_by_size, encoded_len_bytes, RiceCoder};
+#[cfg(feature = "serde")]
+use crate::{DecodeError, MAX_K};
+
+/// A finalized Rice encoded block, bundled with everything needed to decode it.
+///
+/// With the `serde` feature it implements `Serialize` and `Deserialize`, so blocks can be
+/// embedded in larger serialized structures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawRiceBlock"))]
+pub struct RiceBlock {
+    pub k: u8,
+    pub num_vals: u32,
+    pub data: Vec<u8>,
+}
+
+impl RiceBlock {
+    /// Encodes `values` with the `k` picked by `best_k_by_size`.
+    ///
+    /// # Panics
+    /// Panics if there are more than `u32::MAX` values.
+    pub fn encode(values: &[u32]) -> RiceBlock {
+        let num_vals = u32::try_from(values.len()).expect("more than u32::MAX values");
+        let k = best_k_by_size(values);
+        let mut data = Vec::with_capacity(encoded_len_bytes(values, k) as usize);
+        RiceCoder::new(k).encode_vals(values, &mut data);
+        RiceBlock { k, num_vals, data }
+    }
+
+    /// Decodes the values of the block.
+    ///
+    /// # Panics
+    /// Panics if `k` is larger than `MAX_K`. Deserialization already rejects such blocks.
+    pub fn decode(&self) -> Vec<u32> {
+        let mut out =
+            Vec::with_capacity(core::cmp::min(self.num_vals as usize, self.data.len() * 8));
+        RiceCoder::new(self.k).decode_into(&self.data, &mut out, self.num_vals);
+        out
+    }
+}
+
+/// The unvalidated form of a `RiceBlock`, deserialized before `k` is checked.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "RiceBlock")]
+struct RawRiceBlock {
+    k: u8,
+    num_vals: u32,
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawRiceBlock> for RiceBlock {
+    type Error = DecodeError;
+
+    fn try_from(raw: RawRiceBlock) -> Result<Self, DecodeError> {
+        if raw.k > MAX_K {
+            return Err(DecodeError::InvalidK(raw.k));
+        }
+        Ok(RiceBlock {
+            k: raw.k,
+            num_vals: raw.num_vals,
+            data: raw.data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MAX_K;
+    use proptest::prelude::*;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_rice_block_serde() {
+        use serde::de::value::{Error, MapDeserializer};
+        use serde::Deserialize;
+
+        let raw = |k| RawRiceBlock {
+            k,
+            num_vals: 2,
+            data: vec![0b0101_1111],
+        };
+        assert_eq!(RiceBlock::try_from(raw(31)).unwrap().k, 31);
+        assert_eq!(RiceBlock::try_from(raw(32)), Err(DecodeError::InvalidK(32)));
+
+        let deserialize = |fields: &[(&'static str, u32)]| {
+            RiceBlock::deserialize(MapDeserializer::<_, Error>::new(fields.iter().copied()))
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            deserialize(&[("k", 3), ("num_vals", 2)]),
+            "missing field `data`"
+        );
+        assert_eq!(deserialize(&[("k", 3), ("k", 3)]), "duplicate field `k`");
+    }
+
+    #[test]
+    fn test_rice_block_empty() {
+        let block = RiceBlock::encode(&[]);
+        assert_eq!(block.num_vals, 0);
+        assert!(block.decode().is_empty());
+    }
+
+    proptest! {
+        #[test]
+        fn test_rice_block_roundtrip(values in prop::collection::vec(0u32..=100_000, 0..50)) {
+            let block = RiceBlock::encode(&values);
+            prop_assert!(block.k <= MAX_K);
+            prop_assert_eq!(block.num_vals as usize, values.len());
+            prop_assert_eq!(block.decode(), values);
+        }
+    }
+}
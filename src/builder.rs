@@ -0,0 +1,142 @@
+use crate::bits::BitBuffer;
+use crate::{BitOrder, ByteEndian, RiceCoder, UnaryStyle};
+
+/// Builder for a `RiceCoder`, so optional settings compose without a constructor per
+/// combination.
+///
+/// Defaults to `k = 0` without escape codes, zero terminated unary, MSB-first bit order and
+/// big-endian words, like `RiceCoder::default()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RiceCoderBuilder {
+    k: u8,
+    escape: Option<u32>,
+    unary_style: UnaryStyle,
+    bit_order: BitOrder,
+    byte_endian: ByteEndian,
+    max_quotient: Option<u32>,
+    staging: bool,
+}
+
+impl RiceCoderBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of remainder bits
+    pub fn k(mut self, k: u8) -> Self {
+        self.k = k;
+        self
+    }
+
+    /// Enables escape codes for quotients of at least `q_max`, see `RiceCoder::with_escape`.
+    pub fn escape(mut self, q_max: u32) -> Self {
+        self.escape = Some(q_max);
+        self
+    }
+
+    /// Sets how the unary quotient is terminated, see `RiceCoder::with_unary_style`.
+    pub fn unary_style(mut self, style: UnaryStyle) -> Self {
+        self.unary_style = style;
+        self
+    }
+
+    /// Sets the order in which bits are packed into bytes, see `RiceCoder::with_bit_order`.
+    pub fn bit_order(mut self, order: BitOrder) -> Self {
+        self.bit_order = order;
+        self
+    }
+
+    /// Sets the byte order of the 32-bit words of the stream, see `RiceCoder::with_byte_endian`.
+    pub fn byte_endian(mut self, endian: ByteEndian) -> Self {
+        self.byte_endian = endian;
+        self
+    }
+
+    /// Rejects quotients above `max_quotient` when decoding, see `RiceCoder::with_max_quotient`.
+    pub fn max_quotient(mut self, max_quotient: u32) -> Self {
+        self.max_quotient = Some(max_quotient);
+        self
+    }
+
+    /// Stages the encoded bytes before copying them into the output, see
+    /// `RiceCoder::with_staging`.
+    pub fn staging(mut self, staging: bool) -> Self {
+        self.staging = staging;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `k > 31`.
+    #[track_caller]
+    pub fn build(self) -> RiceCoder {
+        let bits = BitBuffer::with_endian(self.bit_order, self.byte_endian);
+        RiceCoder {
+            escape: self.escape,
+            unary: self.unary_style,
+            max_quotient: self.max_quotient,
+            bits: if self.staging { bits.staged() } else { bits },
+            ..RiceCoder::new(self.k)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_defaults() {
+        let coder = RiceCoderBuilder::new().build();
+        assert_eq!(coder, RiceCoder::default());
+        assert_eq!(coder, RiceCoder::new(0));
+        assert_eq!(coder.k(), 0);
+    }
+
+    #[test]
+    fn test_builder_overrides() {
+        assert_eq!(RiceCoderBuilder::new().k(7).build(), RiceCoder::new(7));
+        assert_eq!(
+            RiceCoderBuilder::new().escape(16).k(3).build(),
+            RiceCoder::with_escape(3, 16)
+        );
+        assert_eq!(
+            RiceCoderBuilder::new()
+                .bit_order(BitOrder::LsbFirst)
+                .k(2)
+                .build(),
+            RiceCoder::with_bit_order(2, BitOrder::LsbFirst)
+        );
+        assert_eq!(
+            RiceCoderBuilder::new()
+                .byte_endian(ByteEndian::Little)
+                .k(2)
+                .build(),
+            RiceCoder::with_byte_endian(2, ByteEndian::Little)
+        );
+        let style = UnaryStyle::RunLengthLimited { max: 4 };
+        assert_eq!(
+            RiceCoderBuilder::new().unary_style(style).k(5).build(),
+            RiceCoder::with_unary_style(5, style)
+        );
+        assert_eq!(
+            RiceCoderBuilder::new().max_quotient(100).k(4).build(),
+            RiceCoder::with_max_quotient(4, 100)
+        );
+        assert_eq!(
+            RiceCoderBuilder::new().staging(true).k(6).build(),
+            RiceCoder::with_staging(6)
+        );
+        // The last setting wins
+        assert_eq!(
+            RiceCoderBuilder::new().k(3).k(31).build(),
+            RiceCoder::new(31)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "k must be at most 31")]
+    fn test_builder_k_32_rejected() {
+        RiceCoderBuilder::new().k(32).build();
+    }
+}
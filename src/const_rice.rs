@@ -0,0 +1,134 @@
+use alloc::vec::Vec;
+
+use crate::bits::{BitBuffer, BitReader};
+
+/// Rice coder with `k` known at compile time, so shifts and masks are constant folded.
+///
+/// Produces the same output as `RiceCoder::new(K)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConstRiceCoder<const K: u8> {
+    bits: BitBuffer,
+}
+
+impl<const K: u8> Default for ConstRiceCoder<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const K: u8> ConstRiceCoder<K> {
+    /// Fails to compile for `K > 31`, the shift computing `MASK` would overflow
+    const VALID_K: () = assert!(K <= crate::MAX_K, "k must be at most 31");
+    /// Empty for `K = 0`, i.e. pure unary coding
+    const MASK: u32 = (1 << K) - 1;
+    /// Longest quotient whose shift by `K` keeps all of its bits
+    const MAX_QUOTIENT: u32 = u32::MAX >> K;
+
+    pub fn new() -> Self {
+        let () = Self::VALID_K;
+        ConstRiceCoder {
+            bits: BitBuffer::new(),
+        }
+    }
+
+    pub fn encode_vals(&mut self, values: &[u32], output: &mut Vec<u8>) {
+        for value in values {
+            self.encode(*value, output);
+        }
+        self.finalize(output);
+    }
+
+    /// Rice encoding for a given integer
+    /// Need to call finalize at the end
+    #[inline]
+    pub fn encode(&mut self, value: u32, output: &mut Vec<u8>) {
+        self.bits.write_ones(value >> K, output);
+        self.bits.write_bits(0, 1);
+        self.bits.write_bits(value & Self::MASK, K);
+        self.bits.flush(output);
+    }
+
+    /// Finalize encoding by flushing any remaining bits in the buffer
+    /// We will pad the remaining bits with `1`s to signal the end of the stream.
+    pub fn finalize(&mut self, output: &mut Vec<u8>) {
+        self.bits.finalize(output);
+    }
+
+    /// Rice decoding for `num_values` integers from a byte stream
+    ///
+    /// Stops at a unary run longer than `u32::MAX >> K`, which can only come from corrupt input,
+    /// without reading the rest of the run.
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_into(&self, input: &[u8], out: &mut Vec<u32>, num_values: u32) -> usize {
+        let end_len = out.len().saturating_add(num_values as usize);
+        let mut reader = BitReader::new(input);
+
+        while out.len() < end_len {
+            // Decode unary quotient
+            let Some(quotient) = reader.read_unary(Self::MAX_QUOTIENT.saturating_add(1)) else {
+                break; // Hit EOF marker
+            };
+            if quotient > Self::MAX_QUOTIENT {
+                break; // The value doesn't fit into a `u32`
+            }
+
+            // Decode the binary remainder
+            if let Some(remainder) = reader.read_bits(K) {
+                out.push((quotient << K) + remainder);
+            } else {
+                break; // Not enough bits to complete the number, hit EOF marker
+            }
+        }
+        core::cmp::min(reader.byte_pos() + 1, input.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_rice_coder;
+    use proptest::prelude::*;
+
+    fn test_const_matches_runtime<const K: u8>(values: &[u32]) {
+        let mut encoded: Vec<u8> = Vec::new();
+        ConstRiceCoder::<K>::new().encode_vals(values, &mut encoded);
+
+        let mut expected: Vec<u8> = Vec::new();
+        create_rice_coder(K).encode_vals(values, &mut expected);
+        assert_eq!(encoded, expected);
+
+        let mut decoded_values = Vec::new();
+        let num_bytes = ConstRiceCoder::<K>::new().decode_into(
+            &encoded,
+            &mut decoded_values,
+            values.len() as u32,
+        );
+        assert_eq!(num_bytes, encoded.len());
+        assert_eq!(decoded_values, values);
+    }
+
+    #[test]
+    fn test_const_rice_oversized_run() {
+        // k = 31: the value 5 followed by a run of two `1`s, whose quotient would shift out
+        let input = [0, 0, 0, 0b0000_0101, 0b1100_0000, 0, 0, 0, 0];
+        let mut decoded_values = Vec::new();
+        ConstRiceCoder::<31>::new().decode_into(&input, &mut decoded_values, 10);
+        assert_eq!(decoded_values, [5]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_const_rice_random_values(values in prop::collection::vec(0u32..=500_000, 1..20)) {
+            test_const_matches_runtime::<1>(&values);
+            test_const_matches_runtime::<3>(&values);
+            test_const_matches_runtime::<7>(&values);
+        }
+
+        #[test]
+        fn test_const_rice_k_extremes(values in prop::collection::vec(0u32..=u32::MAX >> 1, 1..20), small in prop::collection::vec(0u32..=100, 1..20)) {
+            test_const_matches_runtime::<0>(&small);
+            test_const_matches_runtime::<31>(&values);
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! CRC-32 trailer to detect corrupt streams, e.g. on disk.
+
+use alloc::vec::Vec;
+
+use crate::{DecodeError, RiceCoder};
+
+/// Reflected polynomial of CRC-32 (IEEE 802.3), as used by zlib and PNG
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+static TABLE: [u32; 256] = build_table();
+
+const fn build_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+}
+
+/// CRC-32 (IEEE 802.3) of `data`
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let crc = data.iter().fold(u32::MAX, |crc, byte| {
+        TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    });
+    !crc
+}
+
+impl RiceCoder {
+    /// Encodes and finalizes the values like `encode_vals`, followed by the CRC-32 of the written
+    /// bytes as 4 little-endian bytes. Decode with `decode_verified_into`.
+    pub fn encode_vals_with_crc(&mut self, values: &[u32], output: &mut Vec<u8>) {
+        let start = output.len();
+        self.encode_vals(values, output);
+        let crc = crc32(&output[start..]);
+        output.extend_from_slice(&crc.to_le_bytes());
+    }
+
+    /// Decodes up to `num_values` values of a stream written by `encode_vals_with_crc`, after
+    /// checking the CRC-32 trailer. The trailer is the last 4 bytes of `input`, so the stream
+    /// can't be followed by other data.
+    ///
+    /// Returns `DecodeError::UnexpectedEof` if the input is too short for the trailer and
+    /// `DecodeError::ChecksumMismatch` if the CRC-32 doesn't match, before decoding any value.
+    /// Otherwise fails like `try_decode_into`.
+    ///
+    /// Returns the number of bytes read, including the trailer
+    pub fn decode_verified_into(
+        &self,
+        input: &[u8],
+        out: &mut Vec<u32>,
+        num_values: u32,
+    ) -> Result<usize, DecodeError> {
+        let body_len = input
+            .len()
+            .checked_sub(4)
+            .ok_or(DecodeError::UnexpectedEof)?;
+        let (body, trailer) = input.split_at(body_len);
+        if crc32(body) != u32::from_le_bytes(trailer.try_into().unwrap()) {
+            return Err(DecodeError::ChecksumMismatch);
+        }
+        self.try_decode_into(body, out, num_values)?;
+        Ok(input.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_rice_coder;
+
+    #[test]
+    fn test_crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(&[]), 0);
+    }
+
+    #[test]
+    fn test_crc_roundtrip_and_corruption() {
+        let values: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        let mut coder = create_rice_coder(3);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals_with_crc(&values, &mut encoded);
+        let mut plain: Vec<u8> = Vec::new();
+        coder.encode_vals(&values, &mut plain);
+        assert_eq!(encoded[..plain.len()], plain);
+        assert_eq!(encoded.len(), plain.len() + 4);
+
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            coder.decode_verified_into(&encoded, &mut decoded_values, values.len() as u32),
+            Ok(encoded.len())
+        );
+        assert_eq!(decoded_values, values);
+
+        // A single flipped bit anywhere, including the trailer, is detected
+        for pos in 0..encoded.len() {
+            let mut corrupt = encoded.clone();
+            corrupt[pos] ^= 0b0001_0000;
+            let mut decoded_values = Vec::new();
+            assert_eq!(
+                coder.decode_verified_into(&corrupt, &mut decoded_values, values.len() as u32),
+                Err(DecodeError::ChecksumMismatch)
+            );
+            assert!(decoded_values.is_empty());
+        }
+        assert_eq!(
+            coder.decode_verified_into(&encoded[..3], &mut decoded_values, 1),
+            Err(DecodeError::UnexpectedEof)
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Cross-checks every decode path against a bit-by-bit reference decoder on the bench datasets,
+//! so a fast path that disagrees with the others fails here instead of shipping silently.
+
+#[cfg(feature = "std")]
+use std::io::BufReader;
+
+use crate::tests::bench_datasets;
+use crate::{decode_pair_into, BitOrder, ByteEndian, ConstRiceCoder, RiceCoder, RiceCoderBuilder};
+
+/// The `k`s the bench datasets are encoded with, covering the table and unary fast paths
+const KS: core::ops::RangeInclusive<u8> = 0..=16;
+
+/// Decodes a finalized MSB-first stream one bit at a time, stopping at the `1`s padding
+fn reference_decode(input: &[u8], k: u8) -> Vec<u32> {
+    let bits: Vec<bool> = input
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |bit| (byte >> bit) & 1 == 1))
+        .collect();
+    let mut values = Vec::new();
+    let mut pos = 0;
+    loop {
+        let Some(quotient) = bits[pos..].iter().position(|bit| !bit) else {
+            return values; // Only the padding is left
+        };
+        let remainder_start = pos + quotient + 1;
+        let Some(remainder) = bits.get(remainder_start..remainder_start + k as usize) else {
+            return values;
+        };
+        let remainder = remainder
+            .iter()
+            .fold(0u32, |acc, bit| (acc << 1) | *bit as u32);
+        values.push(((quotient as u32) << k) | remainder);
+        pos = remainder_start + k as usize;
+    }
+}
+
+/// Dispatches a runtime `k` to `ConstRiceCoder::decode_into`
+fn decode_const(k: u8, input: &[u8], num_values: u32) -> Vec<u32> {
+    macro_rules! decode {
+        ($($k:literal)*) => {
+            match k {
+                $($k => {
+                    let mut out = Vec::new();
+                    ConstRiceCoder::<$k>::new().decode_into(input, &mut out, num_values);
+                    out
+                })*
+                _ => unreachable!("k {} not covered", k),
+            }
+        };
+    }
+    decode!(0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16)
+}
+
+/// Returns the values of every `u32` decode path of `coder` for `encoded`, with the name of the
+/// path. Paths returning the number of bytes read are checked against `encoded.len()`.
+fn decode_paths(
+    coder: &RiceCoder,
+    encoded: &[u8],
+    num_values: u32,
+) -> Vec<(&'static str, Vec<u32>)> {
+    let mut paths = Vec::new();
+    let len = encoded.len();
+
+    let mut out = Vec::new();
+    assert_eq!(coder.decode_into(encoded, &mut out, num_values), len);
+    paths.push(("decode_into", out));
+
+    let mut out = Vec::new();
+    assert_eq!(coder.decode_all_into(encoded, &mut out), len);
+    paths.push(("decode_all_into", out));
+
+    let mut out = Vec::new();
+    let num_bytes = coder.decode_to_sink(encoded, num_values, &mut |value| out.push(value));
+    assert_eq!(num_bytes, len);
+    paths.push(("decode_to_sink", out));
+
+    let mut out = Vec::new();
+    assert_eq!(
+        coder.try_decode_into(encoded, &mut out, num_values),
+        Ok(len)
+    );
+    paths.push(("try_decode_into", out));
+
+    let mut out = Vec::new();
+    assert_eq!(
+        coder.try_decode_verified_into(encoded, &mut out, num_values),
+        Ok(len)
+    );
+    paths.push(("try_decode_verified_into", out));
+
+    let mut out: Vec<u64> = Vec::new();
+    assert_eq!(coder.decode_into_as(encoded, &mut out, num_values), len);
+    paths.push((
+        "decode_into_as::<u64>",
+        out.into_iter().map(|value| value as u32).collect(),
+    ));
+
+    let mut out = vec![0; num_values as usize];
+    let num_written = coder.decode_into_slice(encoded, &mut out);
+    out.truncate(num_written);
+    paths.push(("decode_into_slice", out));
+
+    paths.push((
+        "iter_decode",
+        coder.iter_decode(encoded, num_values).collect(),
+    ));
+
+    let (mut byte_pos, mut bit_pos) = (0, 0);
+    let out = core::iter::from_fn(|| coder.decode_one(encoded, &mut byte_pos, &mut bit_pos))
+        .take(num_values as usize)
+        .collect();
+    paths.push(("decode_one", out));
+
+    let mut out = Vec::new();
+    let num_bytes = coder.decode_chunks(encoded, 7, |chunk| out.extend_from_slice(chunk));
+    assert_eq!(num_bytes, len);
+    paths.push(("decode_chunks", out));
+
+    let mut out = Vec::new();
+    assert_eq!(
+        coder.decode_range_into(encoded, 0, num_values, &mut out),
+        len
+    );
+    paths.push(("decode_range_into", out));
+
+    let mut out = Vec::new();
+    let result = coder.decode_into_result(encoded, &mut out, num_values);
+    assert_eq!(result.values_decoded, out.len());
+    paths.push(("decode_into_result", out));
+
+    let (quotients, remainders) = coder.decode_components(encoded, num_values);
+    let out = quotients
+        .iter()
+        .zip(&remainders)
+        .map(|(quotient, remainder)| (quotient << coder.k()) + remainder)
+        .collect();
+    paths.push(("decode_components", out));
+
+    #[cfg(feature = "std")]
+    {
+        let out = coder
+            .decode_from_reader(BufReader::with_capacity(64, encoded), num_values)
+            .unwrap();
+        paths.push(("decode_from_reader", out));
+    }
+
+    paths
+}
+
+#[test]
+fn test_decode_paths_agree_on_bench_datasets() {
+    for (name, values) in bench_datasets() {
+        let num_values = values.len() as u32;
+        for k in KS {
+            let context = format!("{} k:{}", name, k);
+            let mut coder = RiceCoder::new(k);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+            let expected = reference_decode(&encoded, k);
+            assert_eq!(expected, values, "{}", context);
+
+            for (path, decoded) in decode_paths(&coder, &encoded, num_values) {
+                assert_eq!(decoded, expected, "{} {}", context, path);
+            }
+
+            // A bound on the quotient disables the table and unary fast paths
+            let general = RiceCoder::with_max_quotient(k, u32::MAX);
+            for (path, decoded) in decode_paths(&general, &encoded, num_values) {
+                assert_eq!(decoded, expected, "{} general {}", context, path);
+            }
+
+            let (mut out_a, mut out_b) = (Vec::new(), Vec::new());
+            let num_bytes = decode_pair_into(
+                (&encoded, k),
+                (&encoded, k),
+                &mut out_a,
+                &mut out_b,
+                num_values,
+            );
+            assert_eq!(num_bytes, (encoded.len(), encoded.len()), "{}", context);
+            assert_eq!(out_a, expected, "{} decode_pair_into", context);
+            assert_eq!(out_b, expected, "{} decode_pair_into", context);
+
+            assert_eq!(
+                decode_const(k, &encoded, num_values),
+                expected,
+                "{} ConstRiceCoder",
+                context
+            );
+
+            let mut split: Vec<u8> = Vec::new();
+            let layout = RiceCoder::new(k).encode_split(&values, &mut split);
+            let mut out = Vec::new();
+            assert_eq!(
+                coder.decode_split_into(&split, &layout, &mut out),
+                Ok(split.len())
+            );
+            assert_eq!(out, expected, "{} decode_split_into", context);
+
+            let mut exact: Vec<u8> = Vec::new();
+            coder.encode_vals_no_finalize(&values, &mut exact);
+            let total_bits = coder.finalize_exact(&mut exact);
+            let mut out = Vec::new();
+            coder.decode_bits_into(&exact, total_bits, &mut out);
+            assert_eq!(out, expected, "{} decode_bits_into", context);
+        }
+    }
+}
+
+#[test]
+fn test_decode_paths_agree_across_layouts() {
+    for (name, values) in bench_datasets() {
+        let num_values = values.len() as u32;
+        for k in KS {
+            for order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+                for endian in [ByteEndian::Big, ByteEndian::Little] {
+                    let context = format!("{} k:{} {:?} {:?}", name, k, order, endian);
+                    let mut coder = RiceCoderBuilder::new()
+                        .k(k)
+                        .bit_order(order)
+                        .byte_endian(endian)
+                        .build();
+                    let mut encoded: Vec<u8> = Vec::new();
+                    coder.encode_vals(&values, &mut encoded);
+                    for (path, decoded) in decode_paths(&coder, &encoded, num_values) {
+                        assert_eq!(decoded, values, "{} {}", context, path);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use core::iter::FusedIterator;
+
+use crate::bits::BitReader;
+use crate::RiceCoder;
+
+/// Iterator lazily decoding a Rice stream, created by `RiceCoder::iter_decode`.
+///
+/// Yields at most `num_values` values and stops early if the input is exhausted.
+pub struct RiceDecoder<'a> {
+    coder: &'a RiceCoder,
+    reader: BitReader<'a>,
+    remaining: u32,
+}
+
+impl<'a> RiceDecoder<'a> {
+    pub(crate) fn new(coder: &'a RiceCoder, input: &'a [u8], num_values: u32) -> Self {
+        RiceDecoder {
+            coder,
+            reader: coder.reader(input),
+            remaining: num_values,
+        }
+    }
+}
+
+impl Iterator for RiceDecoder<'_> {
+    type Item = u32;
+
+    #[inline]
+    fn next(&mut self) -> Option<u32> {
+        if self.remaining == 0 {
+            return None;
+        }
+        match self.coder.decode_value(&mut self.reader) {
+            Some(value) => {
+                self.remaining -= 1;
+                Some(value)
+            }
+            None => {
+                self.remaining = 0;
+                None
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining as usize))
+    }
+}
+
+impl FusedIterator for RiceDecoder<'_> {}
+
+/// Iterator lazily decoding a stream written by `RiceCoder::encode_deltas`, yielding the running
+/// sums of the deltas, i.e. the original sorted values. Created by `RiceCoder::iter_decode_deltas`.
+///
+/// Scans e.g. a sorted posting list with O(1) extra memory. Yields the same values as
+/// `RiceCoder::decode_deltas_into`.
+pub struct PrefixSumDecoder<'a> {
+    deltas: RiceDecoder<'a>,
+    sum: u32,
+}
+
+impl<'a> PrefixSumDecoder<'a> {
+    pub(crate) fn new(coder: &'a RiceCoder, input: &'a [u8], num_values: u32) -> Self {
+        PrefixSumDecoder {
+            deltas: RiceDecoder::new(coder, input, num_values),
+            sum: 0,
+        }
+    }
+}
+
+impl Iterator for PrefixSumDecoder<'_> {
+    type Item = u32;
+
+    #[inline]
+    fn next(&mut self) -> Option<u32> {
+        let delta = self.deltas.next()?;
+        self.sum = self.sum.wrapping_add(delta);
+        Some(self.sum)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.deltas.size_hint()
+    }
+}
+
+impl FusedIterator for PrefixSumDecoder<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::create_rice_coder;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_iter_decode_fused() {
+        let values: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        let mut coder = create_rice_coder(3);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&values, &mut encoded);
+
+        let mut iter = coder.iter_decode(&encoded, 3);
+        assert_eq!(iter.by_ref().collect::<Vec<_>>(), [37, 12, 5]);
+        assert_eq!(iter.next(), None);
+
+        let mut iter = coder.iter_decode(&encoded, 100);
+        assert_eq!(iter.by_ref().collect::<Vec<_>>(), values);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_iter_decode_deltas() {
+        let values: Vec<u32> = vec![3, 7, 7, 20, 1000, 1001];
+        let mut coder = create_rice_coder(2);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_deltas(&values, &mut encoded);
+
+        let mut iter = coder.iter_decode_deltas(&encoded, 100);
+        assert_eq!(iter.by_ref().collect::<Vec<_>>(), values);
+        assert_eq!(iter.next(), None);
+    }
+
+    proptest! {
+        #[test]
+        fn test_iter_decode_deltas_matches_prefix_sum(mut values in prop::collection::vec(0u32..=500_000, 0..40), k in 0u8..8) {
+            values.sort_unstable();
+            let mut coder = create_rice_coder(k);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_deltas(&values, &mut encoded);
+
+            let mut deltas = Vec::new();
+            coder.decode_into(&encoded, &mut deltas, values.len() as u32);
+            let prefix_sums: Vec<u32> = deltas
+                .iter()
+                .scan(0u32, |sum, delta| {
+                    *sum = sum.wrapping_add(*delta);
+                    Some(*sum)
+                })
+                .collect();
+            let decoded: Vec<u32> = coder.iter_decode_deltas(&encoded, values.len() as u32).collect();
+            prop_assert_eq!(&decoded, &prefix_sums);
+            prop_assert_eq!(decoded, values);
+        }
+
+        #[test]
+        fn test_iter_decode_matches_decode_into(values in prop::collection::vec(0u32..=500_000, 1..20), k in 1u8..8) {
+            let mut coder = create_rice_coder(k);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+
+            let decoded: Vec<u32> = coder.iter_decode(&encoded, values.len() as u32).collect();
+            prop_assert_eq!(values, decoded);
+        }
+    }
+}
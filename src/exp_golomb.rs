@@ -0,0 +1,131 @@
+use alloc::vec::Vec;
+
+use crate::bits::{BitBuffer, BitReader};
+
+/// Order-k exponential-Golomb coder.
+///
+/// A value `v` is coded via `w = v + 2^k`: the group `n - k`, where `n = floor(log2(w))`, is
+/// written in unary (`1`s terminated by a `0`), followed by the lower `n` bits of `w`.
+/// Compared to Rice coding the code length grows logarithmically instead of linearly with the
+/// value, which suits wide dynamic ranges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpGolombCoder {
+    k: u8,
+    bits: BitBuffer,
+}
+
+impl ExpGolombCoder {
+    /// # Panics
+    ///
+    /// Panics if `k > 31`.
+    pub fn new(k: u8) -> Self {
+        crate::assert_valid_k(k);
+        ExpGolombCoder {
+            k,
+            bits: BitBuffer::new(),
+        }
+    }
+
+    pub fn encode_vals(&mut self, values: &[u32], output: &mut Vec<u8>) {
+        for value in values {
+            self.encode(*value, output);
+        }
+        self.finalize(output);
+    }
+
+    /// Exp-Golomb encoding for a given integer
+    /// Need to call finalize at the end
+    #[inline]
+    pub fn encode(&mut self, value: u32, output: &mut Vec<u8>) {
+        let w = value as u64 + (1u64 << self.k);
+        let n = 63 - w.leading_zeros() as u8; // floor(log2(w)), at most 32
+        let group = n - self.k;
+
+        self.bits.write_ones(group as u32, output);
+        self.bits.write_bits(0, 1);
+        // The leading `1` of `w` is implied by the group
+        self.bits.write_bits((w - (1u64 << n)) as u32, n);
+        self.bits.flush(output);
+    }
+
+    /// Finalize encoding by flushing any remaining bits in the buffer
+    /// We will pad the remaining bits with `1`s to signal the end of the stream.
+    pub fn finalize(&mut self, output: &mut Vec<u8>) {
+        self.bits.finalize(output);
+    }
+
+    /// Exp-Golomb decoding for `num_values` integers from a byte stream
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_into(&self, input: &[u8], out: &mut Vec<u32>, num_values: u32) -> usize {
+        let end_len = out.len().saturating_add(num_values as usize);
+        let mut reader = BitReader::new(input);
+
+        while out.len() < end_len {
+            // Decode unary group, groups beyond 32 - k can't be produced by a u32
+            let max_group = 32 - self.k as u32;
+            let group = reader.read_unary(max_group + 1);
+            let Some(group) = group.filter(|group| *group <= max_group) else {
+                break; // EOF marker or corrupt input
+            };
+
+            let n = group as u8 + self.k;
+            if let Some(suffix) = reader.read_bits(n) {
+                let w = (1u64 << n) + suffix as u64;
+                out.push((w - (1u64 << self.k)) as u32);
+            } else {
+                break; // Not enough bits to complete the number, hit EOF marker
+            }
+        }
+        core::cmp::min(reader.byte_pos() + 1, input.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn test_exp_golomb(k: u8, values: &[u32]) {
+        let mut coder = ExpGolombCoder::new(k);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(values, &mut encoded);
+
+        let mut decoded_values = Vec::new();
+        let num_bytes = coder.decode_into(&encoded, &mut decoded_values, values.len() as u32);
+        assert_eq!(num_bytes, encoded.len());
+        assert_eq!(values, decoded_values);
+    }
+
+    #[test]
+    fn test_exp_golomb_order_0() {
+        // 0 -> 0, 1 -> 10 0, 2 -> 10 1, 3 -> 110 00
+        let mut coder = ExpGolombCoder::new(0);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&[0, 1, 2, 3], &mut encoded);
+        assert_eq!(encoded, [0b0100_1011, 0b1000_1111]);
+        test_exp_golomb(0, &[0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_exp_golomb_extremes() {
+        for k in 0..=31 {
+            test_exp_golomb(k, &[0, u32::MAX, 1, u32::MAX - 1, 1 << 31]);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_exp_golomb_random_values(values in prop::collection::vec(0u32..=500_000, 1..20), k in 1u8..8) {
+            let mut coder = ExpGolombCoder::new(k);
+
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+
+            let mut decoded_values = Vec::new();
+            let num_bytes = coder.decode_into(&encoded, &mut decoded_values, values.len() as u32);
+            prop_assert_eq!(num_bytes, encoded.len());
+            prop_assert_eq!(values, decoded_values);
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Self-describing container for storing a Rice stream in a `.rice` file.
+//!
+//! Layout:
+//! - the 4 magic bytes `FILE_MAGIC`
+//! - the format version `FILE_VERSION` as a byte
+//! - `k` in the low 5 bits of a byte, with bit 7 set if a CRC-32 trailer follows
+//! - the number of values as u32 little-endian
+//! - the finalized Rice codes
+//! - only with bit 7 set, the CRC-32 of all preceding bytes as u32 little-endian
+
+use std::io::{self, Read, Write};
+
+use crate::{DecodeError, RiceCoder, MAX_K};
+
+/// Magic bytes at the start of every `.rice` file
+pub const FILE_MAGIC: [u8; 4] = *b"RICE";
+
+/// Version of the container written by `write_rice_file`
+pub const FILE_VERSION: u8 = 1;
+
+/// Set in the `k` byte if the file ends with a CRC-32 trailer
+const CRC_FLAG: u8 = 0b1000_0000;
+
+const HEADER_LEN: usize = 10;
+
+/// Writes `values` as a `.rice` file, Rice coded with `k`. Read with `read_rice_file`.
+///
+/// Fails with `io::ErrorKind::InvalidInput` for a `k` above 31 or more than `u32::MAX` values.
+pub fn write_rice_file<W: Write>(writer: W, values: &[u32], k: u8) -> io::Result<()> {
+    let file = encode_file(values, k, 0)?;
+    write_all(writer, &file)
+}
+
+/// Like `write_rice_file`, but ends the file with the CRC-32 of its bytes, which
+/// `read_rice_file` verifies.
+#[cfg(feature = "crc")]
+pub fn write_rice_file_with_crc<W: Write>(writer: W, values: &[u32], k: u8) -> io::Result<()> {
+    let mut file = encode_file(values, k, CRC_FLAG)?;
+    let crc = crate::crc::crc32(&file);
+    file.extend_from_slice(&crc.to_le_bytes());
+    write_all(writer, &file)
+}
+
+fn encode_file(values: &[u32], k: u8, flags: u8) -> io::Result<Vec<u8>> {
+    if k > MAX_K {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            DecodeError::InvalidK(k),
+        ));
+    }
+    let num_values = u32::try_from(values.len()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a .rice file holds at most u32::MAX values",
+        )
+    })?;
+    let mut file = Vec::with_capacity(HEADER_LEN + crate::reserve_for(values, k) + 4);
+    file.extend_from_slice(&FILE_MAGIC);
+    file.push(FILE_VERSION);
+    file.push(flags | k);
+    file.extend_from_slice(&num_values.to_le_bytes());
+    RiceCoder::new(k).encode_vals(values, &mut file);
+    Ok(file)
+}
+
+fn write_all<W: Write>(mut writer: W, file: &[u8]) -> io::Result<()> {
+    writer.write_all(file)?;
+    writer.flush()
+}
+
+/// Reads a file written by `write_rice_file` to its end and decodes its values.
+///
+/// Fails with `io::ErrorKind::InvalidData` if the magic bytes don't match, for another version
+/// of the container, an invalid `k`, a CRC-32 that doesn't match or a corrupt stream, and with
+/// `io::ErrorKind::UnexpectedEof` if the file ends before all values are decoded. A file with
+/// CRC-32 trailer fails with `io::ErrorKind::Unsupported` without the `crc` feature.
+pub fn read_rice_file<R: Read>(mut reader: R) -> io::Result<Vec<u32>> {
+    let mut file = Vec::new();
+    reader.read_to_end(&mut file)?;
+    if file.len() < HEADER_LEN {
+        if !file.is_empty() && !FILE_MAGIC.starts_with(&file[..file.len().min(4)]) {
+            return Err(invalid_data("not a .rice file"));
+        }
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            DecodeError::MissingHeader,
+        ));
+    }
+    if file[..4] != FILE_MAGIC {
+        return Err(invalid_data("not a .rice file"));
+    }
+    if file[4] != FILE_VERSION {
+        return Err(invalid_data(DecodeError::UnsupportedVersion(file[4])));
+    }
+    let k = file[5] & !CRC_FLAG;
+    if k > MAX_K {
+        return Err(invalid_data(DecodeError::InvalidK(k)));
+    }
+    let num_values = u32::from_le_bytes(file[6..HEADER_LEN].try_into().unwrap());
+
+    let mut body = &file[HEADER_LEN..];
+    if file[5] & CRC_FLAG != 0 {
+        body = verify_crc(&file)?;
+    }
+    let mut values = Vec::new();
+    RiceCoder::new(k)
+        .try_decode_into(body, &mut values, num_values)
+        .map_err(|err| match err {
+            DecodeError::UnexpectedEof => io::Error::new(io::ErrorKind::UnexpectedEof, err),
+            err => invalid_data(err),
+        })?;
+    if values.len() < num_values as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            DecodeError::CountMismatch {
+                expected: num_values,
+                got: values.len() as u32,
+            },
+        ));
+    }
+    Ok(values)
+}
+
+/// Checks the CRC-32 trailer of `file`, returning the Rice codes before it
+#[cfg(feature = "crc")]
+fn verify_crc(file: &[u8]) -> io::Result<&[u8]> {
+    let Some(body_end) = file.len().checked_sub(4).filter(|&end| end >= HEADER_LEN) else {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            DecodeError::UnexpectedEof,
+        ));
+    };
+    let (checked, trailer) = file.split_at(body_end);
+    if crate::crc::crc32(checked) != u32::from_le_bytes(trailer.try_into().unwrap()) {
+        return Err(invalid_data(DecodeError::ChecksumMismatch));
+    }
+    Ok(&checked[HEADER_LEN..])
+}
+
+#[cfg(not(feature = "crc"))]
+fn verify_crc(_file: &[u8]) -> io::Result<&[u8]> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reading a .rice file with CRC-32 requires the crc feature",
+    ))
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_rice_file_roundtrip() {
+        let values: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        let mut cursor = Cursor::new(Vec::new());
+        write_rice_file(&mut cursor, &values, 3).unwrap();
+        let file = cursor.into_inner();
+        assert_eq!(file[..4], *b"RICE");
+        assert_eq!(file[4..HEADER_LEN], [FILE_VERSION, 3, 7, 0, 0, 0]);
+        let mut body: Vec<u8> = Vec::new();
+        RiceCoder::new(3).encode_vals(&values, &mut body);
+        assert_eq!(file[HEADER_LEN..], body);
+
+        assert_eq!(read_rice_file(Cursor::new(&file)).unwrap(), values);
+
+        let mut file: Vec<u8> = Vec::new();
+        write_rice_file(&mut file, &[], 0).unwrap();
+        assert_eq!(file.len(), HEADER_LEN + 1);
+        assert!(read_rice_file(&file[..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rice_file_errors() {
+        let mut file: Vec<u8> = Vec::new();
+        write_rice_file(&mut file, &[37, 12, 5, 150], 3).unwrap();
+
+        let read_err = |file: &[u8]| read_rice_file(file).unwrap_err();
+        let mut bad_magic = file.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(read_err(&bad_magic).kind(), io::ErrorKind::InvalidData);
+        assert_eq!(read_err(b"PNG").kind(), io::ErrorKind::InvalidData);
+        let mut bad_version = file.clone();
+        bad_version[4] = 2;
+        let err = read_err(&bad_version);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "unsupported header version 2");
+        let mut bad_k = file.clone();
+        bad_k[5] = 40;
+        assert_eq!(read_err(&bad_k).kind(), io::ErrorKind::InvalidData);
+
+        assert_eq!(read_err(&file[..7]).kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(read_err(b"").kind(), io::ErrorKind::UnexpectedEof);
+        // More values announced than stored
+        let mut too_few = file.clone();
+        too_few[6] = 5;
+        assert_eq!(read_err(&too_few).kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(
+            read_err(&file[..HEADER_LEN + 1]).kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        let mut file: Vec<u8> = Vec::new();
+        let err = write_rice_file(&mut file, &[1], 32).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(file.is_empty());
+    }
+
+    #[cfg(feature = "crc")]
+    #[test]
+    fn test_rice_file_crc() {
+        let values: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        let mut file: Vec<u8> = Vec::new();
+        write_rice_file_with_crc(&mut file, &values, 3).unwrap();
+        let mut plain: Vec<u8> = Vec::new();
+        write_rice_file(&mut plain, &values, 3).unwrap();
+        assert_eq!(file.len(), plain.len() + 4);
+        assert_eq!(file[5], CRC_FLAG | 3);
+        assert_eq!(read_rice_file(&file[..]).unwrap(), values);
+
+        // A single flipped bit anywhere after the magic bytes is detected
+        for pos in 4..file.len() {
+            let mut corrupt = file.clone();
+            corrupt[pos] ^= 0b0000_0100;
+            let err = read_rice_file(&corrupt[..]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "pos {}", pos);
+        }
+        assert_eq!(
+            read_rice_file(&file[..HEADER_LEN + 2]).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[cfg(not(feature = "crc"))]
+    #[test]
+    fn test_rice_file_crc_unsupported() {
+        let mut file: Vec<u8> = Vec::new();
+        write_rice_file(&mut file, &[1, 2], 1).unwrap();
+        file[5] |= CRC_FLAG;
+        file.extend_from_slice(&[0; 4]);
+        let err = read_rice_file(&file[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    proptest! {
+        #[test]
+        fn test_rice_file_random_values(values in prop::collection::vec(0u32..=100_000, 0..60), k in 0u8..=16) {
+            let mut cursor = Cursor::new(Vec::new());
+            write_rice_file(&mut cursor, &values, k).unwrap();
+            cursor.set_position(0);
+            prop_assert_eq!(read_rice_file(&mut cursor).unwrap(), values);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use alloc::vec::Vec;
+
+use crate::bits::{BitBuffer, BitReader};
+use crate::varint::{read_varint, varint_len, write_varint};
+use crate::{best_k_by_size, encoded_len_bits, DecodeError, RiceCoder, MAX_K};
+
+/// Mode byte flag of a bit-packed block, the lower bits store the bit width
+const MODE_FIXED: u8 = 0b0100_0000;
+
+/// Encodes `values` either as Rice codes with the best `k`, or bit-packed with the bit width of
+/// the largest value, whichever is smaller. Decode with `decode_block_mode`.
+///
+/// Uniformly distributed values are usually smaller bit-packed. The block starts with a mode
+/// byte: `k` for a Rice block, or `0b0100_0000 | bit_width` for a bit-packed block, followed by
+/// the number of values as LEB128 varint, since its padding can't be told apart from values.
+/// Both are padded with `1`s like `RiceCoder::finalize`.
+pub fn encode_block_mode(values: &[u32], output: &mut Vec<u8>) {
+    let k = best_k_by_size(values);
+    let max_value = values.iter().copied().max().unwrap_or(0);
+    let bit_width = (32 - max_value.leading_zeros()) as u8;
+    let fixed_bits =
+        values.len() as u64 * bit_width as u64 + varint_len(values.len() as u64) as u64 * 8;
+    if fixed_bits < encoded_len_bits(values, k) {
+        output.push(MODE_FIXED | bit_width);
+        write_varint(values.len() as u64, output);
+        let mut bits = BitBuffer::new();
+        for value in values {
+            bits.write_bits(*value, bit_width);
+            bits.flush(output);
+        }
+        bits.finalize(output);
+    } else {
+        output.push(k);
+        RiceCoder::new(k).encode_vals(values, output);
+    }
+}
+
+/// Decodes up to `num_values` values of a block written by `encode_block_mode`, dispatching on
+/// the mode byte.
+///
+/// A bit-packed block stops at its stored number of values. Returns `DecodeError::MissingHeader`
+/// if the number is missing and `DecodeError::UnexpectedEof` if the block ends before it.
+///
+/// Returns the number of bytes read, including the mode byte
+pub fn decode_block_mode(
+    input: &[u8],
+    out: &mut Vec<u32>,
+    num_values: u32,
+) -> Result<usize, DecodeError> {
+    let (&mode, body) = input.split_first().ok_or(DecodeError::MissingHeader)?;
+    if mode & MODE_FIXED == 0 {
+        if mode > MAX_K {
+            return Err(DecodeError::InvalidK(mode));
+        }
+        return Ok(1 + RiceCoder::new(mode).decode_into(body, out, num_values));
+    }
+
+    let bit_width = mode & !MODE_FIXED;
+    if bit_width > 32 {
+        return Err(DecodeError::InvalidMode(mode));
+    }
+    let mut pos = 0;
+    let count = read_varint(body, &mut pos).ok_or(DecodeError::MissingHeader)?;
+    let count = u32::try_from(count).map_err(|_| DecodeError::Overflow)?;
+    let body = &body[pos..];
+    let mut reader = BitReader::new(body);
+    for _ in 0..core::cmp::min(count, num_values) {
+        out.push(
+            reader
+                .read_bits(bit_width)
+                .ok_or(DecodeError::UnexpectedEof)?,
+        );
+    }
+    Ok(1 + pos + core::cmp::min(reader.byte_pos() + 1, body.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_block_mode_picks_fixed_width_for_uniform() {
+        let values: Vec<u32> = (0..256).map(|i| (i * 40_503) % 65_536).collect();
+        let mut encoded: Vec<u8> = Vec::new();
+        encode_block_mode(&values, &mut encoded);
+        assert_eq!(encoded[0], MODE_FIXED | 16);
+        // 256 values of 16 bits, plus mode, count and padding byte
+        assert_eq!(encoded.len(), 1 + 2 + 512 + 1);
+
+        let mut decoded_values = Vec::new();
+        let num_bytes = decode_block_mode(&encoded, &mut decoded_values, 256).unwrap();
+        assert_eq!(num_bytes, encoded.len());
+        assert_eq!(decoded_values, values);
+    }
+
+    #[test]
+    fn test_block_mode_picks_rice_for_skewed() {
+        let values: Vec<u32> = (0..256)
+            .map(|i| if i % 64 == 0 { 60_000 } else { i % 4 })
+            .collect();
+        let mut encoded: Vec<u8> = Vec::new();
+        encode_block_mode(&values, &mut encoded);
+        assert_eq!(encoded[0], best_k_by_size(&values));
+
+        let mut decoded_values = Vec::new();
+        decode_block_mode(&encoded, &mut decoded_values, 256).unwrap();
+        assert_eq!(decoded_values, values);
+    }
+
+    #[test]
+    fn test_block_mode_errors() {
+        let mut out = Vec::new();
+        assert_eq!(
+            decode_block_mode(&[], &mut out, 1),
+            Err(DecodeError::MissingHeader)
+        );
+        assert_eq!(
+            decode_block_mode(&[32], &mut out, 1),
+            Err(DecodeError::InvalidK(32))
+        );
+        assert_eq!(
+            decode_block_mode(&[MODE_FIXED | 33], &mut out, 1),
+            Err(DecodeError::InvalidMode(MODE_FIXED | 33))
+        );
+    }
+
+    #[test]
+    fn test_block_mode_fixed_width_stops_at_count() {
+        // 3 bits each, the padding holds another 3 bits of `1`s
+        let values: Vec<u32> = (0..255).map(|i| [5, 0, 7, 2, 6][i % 5]).collect();
+        let mut encoded: Vec<u8> = Vec::new();
+        encode_block_mode(&values, &mut encoded);
+        assert_eq!(encoded[0], MODE_FIXED | 3);
+        let mut decoded_values = Vec::new();
+        let num_bytes = decode_block_mode(&encoded, &mut decoded_values, u32::MAX).unwrap();
+        assert_eq!(num_bytes, encoded.len());
+        assert_eq!(decoded_values, values);
+
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            decode_block_mode(&encoded[..encoded.len() - 2], &mut decoded_values, u32::MAX),
+            Err(DecodeError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn test_block_mode_width_zero() {
+        let mut encoded: Vec<u8> = Vec::new();
+        encode_block_mode(&[0; 100], &mut encoded);
+        assert_eq!(encoded, [MODE_FIXED, 100, 0xFF]);
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            decode_block_mode(&encoded, &mut decoded_values, 1_000_000),
+            Ok(3)
+        );
+        assert_eq!(decoded_values, [0; 100]);
+
+        // Without the count, a single byte doesn't expand to `num_values` zeros
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            decode_block_mode(&[MODE_FIXED], &mut decoded_values, 1_000_000),
+            Err(DecodeError::MissingHeader)
+        );
+        assert!(decoded_values.is_empty());
+    }
+
+    proptest! {
+        #[test]
+        fn test_block_mode_roundtrip(values in prop::collection::vec(prop_oneof![0u32..16, any::<u32>()], 0..40)) {
+            let mut encoded: Vec<u8> = Vec::new();
+            encode_block_mode(&values, &mut encoded);
+
+            let mut decoded_values = Vec::new();
+            let num_bytes = decode_block_mode(&encoded, &mut decoded_values, values.len() as u32).unwrap();
+            prop_assert_eq!(num_bytes, encoded.len());
+            prop_assert_eq!(decoded_values, values);
+        }
+    }
+}
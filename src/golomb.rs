@@ -0,0 +1,313 @@
+use alloc::vec::Vec;
+
+use crate::bits::{BitBuffer, BitReader};
+use crate::splitmix64;
+
+/// Golomb coder with an arbitrary divisor `m`, Rice coding is the special case `m = 2^k`.
+///
+/// A value `v` is coded as the quotient `v / m` in unary (`1`s terminated by a `0`), followed by
+/// the remainder `v % m` in truncated binary: with `b = ceil(log2(m))`, the first
+/// `2^b - m` remainders take `b - 1` bits, the others `b` bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GolombCoder {
+    m: u32,
+    /// `ceil(log2(m))`
+    b: u8,
+    /// Remainders below `2^b - m` are written with `b - 1` bits
+    cutoff: u32,
+    bits: BitBuffer,
+}
+
+impl GolombCoder {
+    /// # Panics
+    ///
+    /// Panics if `m == 0`.
+    pub fn new(m: u32) -> Self {
+        assert!(m > 0, "Golomb divisor must be positive");
+        let b = 32 - (m - 1).leading_zeros() as u8;
+        let cutoff = ((1u64 << b) - m as u64) as u32;
+        GolombCoder {
+            m,
+            b,
+            cutoff,
+            bits: BitBuffer::new(),
+        }
+    }
+
+    pub fn m(&self) -> u32 {
+        self.m
+    }
+
+    pub fn encode_vals(&mut self, values: &[u32], output: &mut Vec<u8>) {
+        for value in values {
+            self.encode(*value, output);
+        }
+        self.finalize(output);
+    }
+
+    /// Golomb encoding for a given integer
+    /// Need to call finalize at the end
+    #[inline]
+    pub fn encode(&mut self, value: u32, output: &mut Vec<u8>) {
+        let quotient = value / self.m;
+        let remainder = value % self.m;
+
+        self.bits.write_ones(quotient, output);
+        self.bits.write_bits(0, 1);
+        if remainder < self.cutoff {
+            self.bits.write_bits(remainder, self.b - 1);
+        } else {
+            self.bits.write_bits(remainder + self.cutoff, self.b);
+        }
+        self.bits.flush(output);
+    }
+
+    /// Finalize encoding by flushing any remaining bits in the buffer
+    /// We will pad the remaining bits with `1`s to signal the end of the stream.
+    pub fn finalize(&mut self, output: &mut Vec<u8>) {
+        self.bits.finalize(output);
+    }
+
+    /// Golomb decoding for `num_values` integers from a byte stream
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_into(&self, input: &[u8], out: &mut Vec<u32>, num_values: u32) -> usize {
+        let end_len = out.len().saturating_add(num_values as usize);
+        let mut reader = BitReader::new(input);
+
+        while out.len() < end_len {
+            let Some(value) = self.decode_value(&mut reader) else {
+                break; // Hit EOF marker
+            };
+            out.push(value);
+        }
+        core::cmp::min(reader.byte_pos() + 1, input.len())
+    }
+
+    #[inline]
+    fn decode_value(&self, reader: &mut BitReader) -> Option<u32> {
+        let quotient = reader.read_unary(u32::MAX)?;
+        let remainder = if self.b == 0 {
+            0 // m = 1, there is no remainder
+        } else {
+            let prefix = reader.read_bits(self.b - 1)?;
+            if prefix < self.cutoff {
+                prefix
+            } else {
+                ((prefix << 1) | reader.read_bit()? as u32) - self.cutoff
+            }
+        };
+        // Only corrupt input can exceed `u32::MAX`
+        u32::try_from(quotient as u64 * self.m as u64 + remainder as u64).ok()
+    }
+}
+
+/// Estimates the Golomb divisor from the mean of the values, `m = ceil(ln(2) * mean)`, which
+/// is near-optimal for geometrically distributed values.
+pub fn best_m(values: &[u32]) -> u32 {
+    if values.is_empty() {
+        return 1;
+    }
+    let sum: u64 = values.iter().map(|&value| value as u64).sum();
+    // ln(2) ~= 0.693147
+    let denominator = values.len() as u128 * 1_000_000;
+    let m = (sum as u128 * 693_147).div_ceil(denominator);
+    m.clamp(1, u32::MAX as u128) as u32
+}
+
+/// Returns the exact number of bits the Golomb codes with divisor `m` for `values` occupy,
+/// excluding the padding written by `finalize`.
+fn golomb_len_bits(values: &[u32], m: u32) -> u64 {
+    let b = 32 - (m - 1).leading_zeros() as u64;
+    let cutoff = (1u64 << b) - m as u64;
+    values
+        .iter()
+        .map(|&value| {
+            let remainder_bits = if ((value % m) as u64) < cutoff {
+                b - 1
+            } else {
+                b
+            };
+            (value / m) as u64 + 1 + remainder_bits
+        })
+        .sum()
+}
+
+/// Refines `best_m` by simulated annealing on the exact encoded size, which is much cheaper than
+/// trying every divisor when the values are large.
+///
+/// Each of the `iterations` steps moves `m` up or down by a random step, which shrinks from a
+/// quarter of `m` to 1. A move is taken if it grows the size by less than a threshold, which
+/// starts at 1% of the size and falls to 0, so early moves can escape local minima. Returns the
+/// smallest `m` seen. The steps are drawn with a fixed seed, so the result is reproducible.
+pub fn best_m_annealing(values: &[u32], iterations: u32) -> u32 {
+    let mut m = best_m(values);
+    let mut len = golomb_len_bits(values, m);
+    let (mut best, mut best_len) = (m, len);
+    let start_threshold = len as f64 / 100.0;
+    let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+    for i in 0..iterations {
+        let remaining = 1.0 - i as f64 / iterations as f64;
+        let max_step = ((m / 4) as f64 * remaining).max(1.0) as u64;
+        let random = splitmix64(&mut state);
+        let step = (1 + (random >> 1) % max_step) as u32;
+        let candidate = if random & 1 == 0 {
+            m.saturating_add(step)
+        } else {
+            core::cmp::max(m.saturating_sub(step), 1)
+        };
+        let candidate_len = golomb_len_bits(values, candidate);
+        if (candidate_len as f64) < len as f64 + start_threshold * remaining {
+            m = candidate;
+            len = candidate_len;
+            if len < best_len {
+                best = m;
+                best_len = len;
+            }
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{best_k_by_size, encoded_len_bytes};
+    use proptest::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn test_golomb(m: u32, values: &[u32]) -> usize {
+        let mut coder = GolombCoder::new(m);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(values, &mut encoded);
+
+        let mut decoded_values = Vec::new();
+        let num_bytes = coder.decode_into(&encoded, &mut decoded_values, values.len() as u32);
+        assert_eq!(num_bytes, encoded.len());
+        assert_eq!(values, decoded_values);
+        encoded.len()
+    }
+
+    #[test]
+    fn test_golomb_truncated_binary() {
+        // m = 5, b = 3, cutoff = 3: remainders 0..3 take 2 bits, 3 and 4 take 3 bits
+        // 0 -> 0 00, 2 -> 0 10, 3 -> 0 110, 4 -> 0 111, 7 -> 10 10
+        let mut coder = GolombCoder::new(5);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&[0, 2, 3, 4, 7], &mut encoded);
+        assert_eq!(encoded, [0b0000_1001, 0b1001_1110, 0b1011_1111]);
+        test_golomb(5, &[0, 2, 3, 4, 7]);
+    }
+
+    #[test]
+    fn test_golomb_matches_rice_for_powers_of_two() {
+        let values = [0, 1, 7, 8, 9, 100, 1000];
+        for k in 0..8 {
+            let mut encoded: Vec<u8> = Vec::new();
+            GolombCoder::new(1 << k).encode_vals(&values, &mut encoded);
+            let mut expected: Vec<u8> = Vec::new();
+            crate::RiceCoder::new(k).encode_vals(&values, &mut expected);
+            assert_eq!(encoded, expected);
+        }
+    }
+
+    #[test]
+    fn test_golomb_extremes() {
+        test_golomb(1, &[0, 1, 5, 100]);
+        test_golomb(2, &[0, 1, 2, 3, 1000]);
+        test_golomb(3, &[0, 1, 2, 3, 1000]);
+        for m in [(1 << 31) - 1, 1 << 31, (1 << 31) + 1, u32::MAX] {
+            test_golomb(m, &[0, u32::MAX, m - 1, u32::MAX - 1, 1 << 31]);
+        }
+    }
+
+    #[test]
+    fn test_best_m_beats_rice_on_geometric() {
+        // Geometric with success probability 1/66, the ideal divisor is 46, halfway between two powers of two
+        let mut rng = StdRng::from_seed([3; 32]);
+        let values: Vec<u32> = (0..10_000)
+            .map(|_| {
+                let mut value = 0;
+                while rng.gen_range(0..66) != 0 {
+                    value += 1;
+                }
+                value
+            })
+            .collect();
+        let m = best_m(&values);
+        assert!((43..=49).contains(&m), "m = {}", m);
+        let golomb_len = test_golomb(m, &values);
+        let k = best_k_by_size(&values);
+        let rice_len = encoded_len_bytes(&values, k) as usize;
+        assert!(golomb_len < rice_len);
+    }
+
+    #[test]
+    fn test_best_m_small() {
+        assert_eq!(best_m(&[]), 1);
+        assert_eq!(best_m(&[0, 0, 0]), 1);
+        assert_eq!(best_m(&[u32::MAX; 4]), 2_977_043_696);
+    }
+
+    #[test]
+    fn test_golomb_len_bits() {
+        let values = [0, 2, 3, 4, 7];
+        let mut encoded: Vec<u8> = Vec::new();
+        GolombCoder::new(5).encode_vals(&values, &mut encoded);
+        // 0 00, 0 10, 0 110, 0 111, 10 10, followed by 6 padding bits
+        assert_eq!(golomb_len_bits(&values, 5), 18);
+        assert_eq!(
+            golomb_len_bits(&values, 5).div_ceil(8),
+            encoded.len() as u64
+        );
+        assert_eq!(golomb_len_bits(&[5, u32::MAX], 1), 6 + u32::MAX as u64 + 1);
+    }
+
+    #[test]
+    fn test_best_m_annealing_near_exhaustive() {
+        for (name, values) in crate::tests::bench_datasets() {
+            // Beyond `max + 1` every quotient is 0 and the remainders only grow
+            let max = *values.iter().max().unwrap();
+            let exhaustive_len = (1..=max + 1)
+                .map(|m| golomb_len_bits(&values, m))
+                .min()
+                .unwrap();
+            let m = best_m_annealing(&values, 200);
+            let len = golomb_len_bits(&values, m);
+            assert!(len <= golomb_len_bits(&values, best_m(&values)), "{}", name);
+            assert!(
+                len as f64 <= exhaustive_len as f64 * 1.01,
+                "{}: m = {}, {} bits instead of {}",
+                name,
+                m,
+                len,
+                exhaustive_len
+            );
+            assert_eq!(best_m_annealing(&values, 200), m, "{}", name);
+        }
+        assert_eq!(best_m_annealing(&[], 10), 1);
+        assert_eq!(best_m_annealing(&[0, 0, 0], 0), 1);
+    }
+
+    proptest! {
+        #[test]
+        fn test_golomb_random_values(values in prop::collection::vec(0u32..=100_000, 1..40), m in 2u32..=64) {
+            test_golomb(m, &values);
+        }
+
+        #[test]
+        fn test_golomb_remainder_edges(quotients in prop::collection::vec(0u32..20, 1..40), m in 2u32..=64) {
+            // Remainders around the truncated binary cutoff and at both ends
+            let b = 32 - (m - 1).leading_zeros();
+            let cutoff = (1 << b) - m;
+            let remainders = [0, cutoff.saturating_sub(1), cutoff, m - 1];
+            let values: Vec<u32> = quotients
+                .iter()
+                .enumerate()
+                .map(|(i, quotient)| quotient * m + core::cmp::min(remainders[i % 4], m - 1))
+                .collect();
+            test_golomb(m, &values);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+/// Integer types Rice codes can be decoded into, see `RiceCoder::decode_into_as`.
+///
+/// Provides rebuilding a value from its unary quotient and `k` bit remainder, so values are
+/// decoded directly into the target type without a separate conversion pass.
+pub trait RiceInt: Copy {
+    /// The largest quotient for `k` such that `(quotient << k) | remainder` fits into the type.
+    /// Longer unary runs are rejected without reading them completely.
+    fn max_quotient(k: u8) -> u32;
+
+    /// Returns `(quotient << k) | remainder`. `quotient` is at most `max_quotient(k)`.
+    fn from_parts(quotient: u32, remainder: u32, k: u8) -> Self;
+}
+
+impl RiceInt for u32 {
+    #[inline]
+    fn max_quotient(k: u8) -> u32 {
+        u32::MAX >> k
+    }
+
+    #[inline]
+    fn from_parts(quotient: u32, remainder: u32, k: u8) -> Self {
+        (quotient << k) | remainder
+    }
+}
+
+impl RiceInt for u64 {
+    #[inline]
+    fn max_quotient(_k: u8) -> u32 {
+        u32::MAX // `(u32::MAX << 31) | remainder` still fits
+    }
+
+    #[inline]
+    fn from_parts(quotient: u32, remainder: u32, k: u8) -> Self {
+        ((quotient as u64) << k) | remainder as u64
+    }
+}
+
+impl RiceInt for usize {
+    #[inline]
+    fn max_quotient(k: u8) -> u32 {
+        core::cmp::min(usize::MAX >> k, u32::MAX as usize) as u32
+    }
+
+    #[inline]
+    fn from_parts(quotient: u32, remainder: u32, k: u8) -> Self {
+        ((quotient as usize) << k) | remainder as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_parts() {
+        assert_eq!(u32::from_parts(3, 5, 4), 53);
+        assert_eq!(u64::from_parts(3, 5, 4), 53);
+        assert_eq!(usize::from_parts(3, 5, 4), 53);
+        assert_eq!(
+            u32::from_parts(u32::max_quotient(31), u32::MAX >> 1, 31),
+            u32::MAX
+        );
+        assert_eq!(
+            u64::from_parts(u64::max_quotient(31), 0, 31),
+            (u32::MAX as u64) << 31
+        );
+    }
+}
@@ -1,250 +1,4214 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+mod bits;
+mod block;
+mod builder;
+mod const_rice;
+#[cfg(feature = "crc")]
+mod crc;
+#[cfg(test)]
+mod decode_equivalence;
+mod decoder;
+mod exp_golomb;
+#[cfg(feature = "std")]
+mod file;
+mod fixed;
+mod golomb;
+mod int;
+#[cfg(feature = "rayon")]
+mod par;
+#[cfg(feature = "std")]
+mod reader;
+mod rice64;
+mod segment;
+#[cfg(feature = "simd")]
+mod simd;
+mod split;
+mod stream;
+mod table;
+#[cfg(feature = "std")]
+mod timing;
+mod varint;
+mod view;
+#[cfg(feature = "std")]
+mod writer;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use bits::{BitBuffer, BitReader};
+pub use bits::{BitOrder, BitWriter, ByteEndian};
+pub use block::RiceBlock;
+pub use builder::RiceCoderBuilder;
+pub use const_rice::ConstRiceCoder;
+use core::ops::Range;
+pub use decoder::{PrefixSumDecoder, RiceDecoder};
+pub use exp_golomb::ExpGolombCoder;
+#[cfg(all(feature = "std", feature = "crc"))]
+pub use file::write_rice_file_with_crc;
+#[cfg(feature = "std")]
+pub use file::{read_rice_file, write_rice_file, FILE_MAGIC, FILE_VERSION};
+pub use fixed::{decode_block_mode, encode_block_mode};
+pub use golomb::{best_m, best_m_annealing, GolombCoder};
+pub use int::RiceInt;
+#[cfg(feature = "rayon")]
+pub use par::encode_blocks_par;
+pub use rice64::RiceCoder64;
+pub use segment::{auto_block_encode, decode_auto_blocks};
+pub use split::SplitLayout;
+pub use stream::{RiceStreamBuilder, RiceStreamDecoder};
+#[cfg(feature = "std")]
+pub use timing::DecodeTiming;
+use varint::{read_varint, varint_len, write_varint};
+pub use view::RiceView;
+#[cfg(feature = "std")]
+pub use writer::RiceWriter;
+
 /// Function to estimate the optimal `k` based on a given percentile.
 /// `values`: slice of input values to process.
-/// `percentile`: desired percentile (e.g., 50.0 for median, 90.0 for 90th percentile).
-pub fn estimate_optimal_k(values: &[u32], percentile: usize) -> u8 {
+/// `percentile`: desired percentile in `0.0..=100.0` (e.g., 50.0 for median, 90.0 for 90th
+/// percentile). Percentiles outside the range are clamped to it.
+///
+/// The percentile value is linearly interpolated between the sorted values: with
+/// `rank = percentile / 100 * (len - 1)`, it is
+/// `sorted[floor(rank)] + fract(rank) * (sorted[ceil(rank)] - sorted[floor(rank)])`, truncated to
+/// an integer. `k` is the number of bits of that value.
+///
+/// # Panics
+///
+/// Panics if `percentile` is NaN.
+pub fn estimate_optimal_k(values: &[u32], percentile: f64) -> u8 {
+    assert!(!percentile.is_nan(), "percentile must not be NaN");
     // Ensure there are values to process
     if values.is_empty() {
         return 0;
     }
+    let percentile = percentile.clamp(0.0, 100.0);
+
+    // Sort the values
+    let mut sorted_values = values.to_vec();
+    sorted_values.sort_unstable();
+
+    // Determine the rank of the desired percentile
+    let (lower_index, upper_index, fraction) = percentile_rank(sorted_values.len(), percentile);
+
+    // Interpolate the value at the desired percentile
+    let lower = sorted_values[lower_index] as f64;
+    let upper = sorted_values[upper_index] as f64;
+    let value_at_percentile = (lower + fraction * (upper - lower)) as u32;
+
+    // Use the log2 of the percentile value to estimate k
+    (32 - value_at_percentile.leading_zeros()) as u8
+}
+
+/// Returns the two indices to interpolate between for `percentile` in `0.0..=100.0` of `len > 0`
+/// sorted values, and the fraction of the way from the lower to the upper one.
+///
+/// The rank is computed in `f64`, so it can't overflow. For lengths beyond 2^53, `len - 1` isn't
+/// exactly representable and may round up, so both indices are clamped to `len - 1`.
+#[inline]
+fn percentile_rank(len: usize, percentile: f64) -> (usize, usize, f64) {
+    let last_index = len - 1;
+    let rank = percentile / 100.0 * last_index as f64;
+    // Truncation is floor for non-negative values, and saturates at `usize::MAX`
+    let lower_index = core::cmp::min(rank as usize, last_index);
+    let upper_index = core::cmp::min(lower_index + 1, last_index);
+    let fraction = rank - lower_index as f64;
+    (lower_index, upper_index, fraction)
+}
+
+/// Like `estimate_optimal_k`, but without sorting: a single pass builds a histogram of the bit
+/// lengths, so it takes O(n) time and O(1) extra space and returns the same `k`.
+///
+/// Besides the count, each of the 33 buckets tracks its smallest and largest value. When the
+/// two values to interpolate between fall into different buckets, they are the largest value of
+/// the lower bucket and the smallest value of the upper one.
+///
+/// # Panics
+///
+/// Panics if `percentile` is NaN.
+pub fn estimate_optimal_k_hist(values: &[u32], percentile: f64) -> u8 {
+    assert!(!percentile.is_nan(), "percentile must not be NaN");
+    if values.is_empty() {
+        return 0;
+    }
+    let percentile = percentile.clamp(0.0, 100.0);
+
+    let mut counts = [0usize; 33];
+    let mut mins = [u32::MAX; 33];
+    let mut maxs = [0u32; 33];
+    for &value in values {
+        let bucket = (32 - value.leading_zeros()) as usize;
+        counts[bucket] += 1;
+        mins[bucket] = core::cmp::min(mins[bucket], value);
+        maxs[bucket] = core::cmp::max(maxs[bucket], value);
+    }
+    // The bucket of the value at `index` in sorted order
+    let bucket_of = |index: usize| {
+        let mut cumulative = 0;
+        counts
+            .iter()
+            .position(|count| {
+                cumulative += count;
+                cumulative > index
+            })
+            .unwrap_or(32)
+    };
+
+    // Same rank as `estimate_optimal_k`
+    let (lower_index, upper_index, fraction) = percentile_rank(values.len(), percentile);
+
+    let lower_bucket = bucket_of(lower_index);
+    let upper_bucket = bucket_of(upper_index);
+    if lower_bucket == upper_bucket {
+        // Any value between two values of the same bit length has that bit length
+        return lower_bucket as u8;
+    }
+    // Adjacent in sorted order, but in different buckets
+    let lower = maxs[lower_bucket] as f64;
+    let upper = mins[upper_bucket] as f64;
+    let value_at_percentile = (lower + fraction * (upper - lower)) as u32;
+    (32 - value_at_percentile.leading_zeros()) as u8
+}
+
+/// Approximates `estimate_optimal_k` for streams too large to buffer and sort, by applying it to
+/// a uniform reservoir sample of at most `sample_size` values.
+///
+/// The sample is drawn with a fixed seed, so the result is reproducible. If the stream has at
+/// most `sample_size` values, the result is the same as `estimate_optimal_k`.
+///
+/// # Panics
+///
+/// Panics if `sample_size == 0` or `percentile` is NaN.
+pub fn estimate_k_reservoir<I: Iterator<Item = u32>>(
+    values: I,
+    sample_size: usize,
+    percentile: f64,
+) -> u8 {
+    assert!(sample_size > 0, "sample_size must be positive");
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    let mut reservoir = Vec::with_capacity(sample_size);
+    for (i, value) in values.enumerate() {
+        if i < sample_size {
+            reservoir.push(value);
+            continue;
+        }
+        // Keep the value with probability `sample_size / (i + 1)`
+        let j = ((splitmix64(&mut state) as u128 * (i as u128 + 1)) >> 64) as usize;
+        if j < sample_size {
+            reservoir[j] = value;
+        }
+    }
+    estimate_optimal_k(&reservoir, percentile)
+}
+
+/// SplitMix64, good enough for sampling and searching and without dependencies
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Returns the exact number of bits the Rice codes for `values` occupy, excluding the padding
+/// written by `finalize`.
+pub fn encoded_len_bits(values: &[u32], k: u8) -> u64 {
+    values
+        .iter()
+        .map(|value| (value >> k) as u64 + 1 + k as u64)
+        .sum()
+}
+
+/// Returns the exact number of bytes `RiceCoder::encode_vals` writes for `values` with the
+/// default `ByteEndian::Big`.
+///
+/// `finalize` always writes at least one padding bit, so a stream ending on a byte boundary gets
+/// a full padding byte. With `ByteEndian::Little` it pads to a whole word instead, so the output
+/// is this size rounded up to a multiple of 4, up to 3 bytes more.
+pub fn encoded_len_bytes(values: &[u32], k: u8) -> u64 {
+    encoded_len_bits(values, k) / 8 + 1
+}
+
+/// Returns the number of bytes to reserve in the output before encoding `values` with `k`, so
+/// `RiceCoder::encode_vals` doesn't reallocate. Same as `encoded_len_bytes`, so for
+/// `ByteEndian::Little` reserve `reserve_for(values, k).next_multiple_of(4)` instead.
+///
+/// `encode_vals` reserves this size on its own. Reserve it up front where the output holds more
+/// than the Rice codes, e.g. `Vec::with_capacity(header_len + reserve_for(values, k))`. Only
+/// exact for a plain `RiceCoder::new(k)`: with escape codes, the size can be far smaller than
+/// the plain Rice codes.
+pub fn reserve_for(values: &[u32], k: u8) -> usize {
+    usize::try_from(encoded_len_bytes(values, k)).unwrap_or(usize::MAX)
+}
+
+/// Returns the size of `values` as plain 4-byte values divided by the size of their Rice codes
+/// with `k`, as computed by `encoded_len_bytes` without actually encoding. Values above 1 mean
+/// Rice coding is smaller.
+pub fn compression_ratio(values: &[u32], k: u8) -> f64 {
+    (values.len() as u64 * 4) as f64 / encoded_len_bytes(values, k) as f64
+}
+
+/// Sizes in bytes reported by `savings_vs_fixed`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SavingsReport {
+    /// Size as plain 4-byte values
+    pub plain_bytes: u64,
+    /// Size with every value in the fewest whole bytes, at least one, fitting the largest value
+    pub fixed_width_bytes: u64,
+    /// The `k` picked by `best_k_by_size`
+    pub best_k: u8,
+    /// Size of the Rice codes with `best_k`, including the padding of `ByteEndian::Big`.
+    /// `ByteEndian::Little` pads to a whole word, which adds up to 3 bytes.
+    pub rice_bytes: u64,
+    /// Share of `fixed_width_bytes` saved by Rice coding in percent, negative if the Rice codes
+    /// are larger, and 0 without values
+    pub percent_saved: f64,
+}
+
+/// Estimates how much smaller Rice coding with the best `k` is than a fixed byte width per value,
+/// e.g. to decide whether the codec is worth adopting. Computed with `encoded_len_bytes`, without
+/// actually encoding.
+pub fn savings_vs_fixed(values: &[u32]) -> SavingsReport {
+    let num_values = values.len() as u64;
+    let max = values.iter().copied().max().unwrap_or(0);
+    let width = core::cmp::max((32 - max.leading_zeros() as u64).div_ceil(8), 1);
+    let fixed_width_bytes = num_values * width;
+    let best_k = best_k_by_size(values);
+    let rice_bytes = encoded_len_bytes(values, best_k);
+    let percent_saved = if fixed_width_bytes == 0 {
+        0.0
+    } else {
+        (1.0 - rice_bytes as f64 / fixed_width_bytes as f64) * 100.0
+    };
+    SavingsReport {
+        plain_bytes: num_values * 4,
+        fixed_width_bytes,
+        best_k,
+        rice_bytes,
+        percent_saved,
+    }
+}
+
+/// Returns the exact encoded size in bits, as computed by `encoded_len_bits`, for every `k` in
+/// `0..=31`, e.g. to inspect the tradeoff instead of only the `k` picked by `best_k_by_size`.
+pub fn k_size_table(values: &[u32]) -> Vec<(u8, u64)> {
+    (0..=MAX_K)
+        .map(|k| (k, encoded_len_bits(values, k)))
+        .collect()
+}
+
+/// Returns the `k` in `0..=31` that minimizes the exact encoded size of `values`.
+///
+/// The encoded size is convex in `k`, so the search stops as soon as increasing `k` no longer
+/// reduces the size.
+pub fn best_k_by_size(values: &[u32]) -> u8 {
+    let mut best_k = 0;
+    let mut best_len = encoded_len_bits(values, 0);
+    for k in 1..=31 {
+        let len = encoded_len_bits(values, k);
+        if len >= best_len {
+            break;
+        }
+        best_k = k;
+        best_len = len;
+    }
+    best_k
+}
+
+/// Estimated encoded sizes of a set of values, returned by `analyze`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodingReport {
+    /// The `k` picked by `best_k_by_size`
+    pub k: u8,
+    /// Bytes of the Rice codes with `k`, as written by `RiceCoder::encode_vals`
+    pub rice_bytes: u64,
+    /// Bytes of plain 4-byte values
+    pub raw_bytes: u64,
+    /// Bytes of LEB128 varints
+    pub varint_bytes: u64,
+}
+
+/// Compares the size of Rice coding with the best `k` against raw and varint encoding, to decide
+/// whether Rice coding is worth it for `values`.
+///
+/// The Rice size is computed via `encoded_len_bytes` without actually encoding.
+pub fn analyze(values: &[u32]) -> EncodingReport {
+    let k = best_k_by_size(values);
+    EncodingReport {
+        k,
+        rice_bytes: encoded_len_bytes(values, k),
+        raw_bytes: values.len() as u64 * 4,
+        varint_bytes: values
+            .iter()
+            .map(|&value| varint_len(value as u64) as u64)
+            .sum(),
+    }
+}
+
+/// Estimates `k` from the mean of the values using the Golomb parameter for geometric
+/// distributions, `k = floor(log2(ln(2) * mean))`, clamped to `0..=31`.
+///
+/// This is a single pass without allocations and is near-optimal if the values are roughly
+/// geometrically distributed. For other distributions prefer `best_k_by_size`.
+pub fn estimate_k_from_mean(values: &[u32]) -> u8 {
+    estimate_k_streaming(values.iter().copied())
+}
+
+/// Like `estimate_k_from_mean`, but for values that are not buffered: accumulates the sum and
+/// count in a single pass over the iterator.
+pub fn estimate_k_streaming<I: Iterator<Item = u32>>(values: I) -> u8 {
+    let (sum, count) = values.fold((0u128, 0u64), |(sum, count), value| {
+        (sum + value as u128, count + 1)
+    });
+    if count == 0 {
+        return 0;
+    }
+    k_from_sum(sum, count)
+}
+
+/// `floor(log2(ln(2) * sum / count))` in integer arithmetic
+fn k_from_sum(sum: u128, count: u64) -> u8 {
+    // ln(2) ~= 0.693147
+    let scaled_mean = (sum * 693_147) / (count as u128 * 1_000_000);
+    if scaled_mean == 0 {
+        return 0;
+    }
+    core::cmp::min(127 - scaled_mean.leading_zeros(), 31) as u8
+}
+
+/// Version of the header written by `RiceCoder::encode_with_header`
+pub const HEADER_VERSION: u8 = 1;
+
+/// Largest unary quotient `value >> k` accepted by `RiceCoder::checked_encode`.
+///
+/// This bounds the encoded size of a single value to `MAX_QUOTIENT + 1 + k` bits (about 8KiB).
+pub const MAX_QUOTIENT: u32 = u16::MAX as u32;
+
+/// Errors that can occur while encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The quotient `value >> k` exceeds `MAX_QUOTIENT`
+    QuotientTooLarge { value: u32, k: u8 },
+    /// A value passed to `checked_encode_with_offset` is below the offset
+    BelowOffset { value: u32, offset: u32 },
+    /// The quotient `value >> k` exceeds the `max` of `UnaryStyle::RunLengthLimited`
+    RunTooLong { value: u32, max: u32 },
+}
+
+impl core::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EncodeError::QuotientTooLarge { value, k } => write!(
+                f,
+                "value {} exceeds the maximum quotient {} for k={}",
+                value, MAX_QUOTIENT, k
+            ),
+            EncodeError::BelowOffset { value, offset } => {
+                write!(f, "value {} is below the offset {}", value, offset)
+            }
+            EncodeError::RunTooLong { value, max } => write!(
+                f,
+                "value {} exceeds the unary run length limit {}",
+                value, max
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EncodeError {}
+
+/// Errors that can occur while decoding a Rice stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input is too short to contain the header
+    MissingHeader,
+    /// The header was written by an unsupported format version
+    UnsupportedVersion(u8),
+    /// The header contains a `k` larger than 31
+    InvalidK(u8),
+    /// The header of an adaptive stream contains a block size of zero
+    InvalidBlockSize,
+    /// The mode byte of a block written by `encode_block_mode` is invalid
+    InvalidMode(u8),
+    /// The input ends in the middle of a value
+    UnexpectedEof,
+    /// A decoded value doesn't fit into a `u32`
+    Overflow,
+    /// The stream ends after `got` values, but `expected` values were requested
+    CountMismatch { expected: u32, got: u32 },
+    /// The bits after the last value are not the `1`s padding written by `finalize`
+    BadPadding,
+    /// The header of a matrix contains other dimensions than requested
+    DimensionMismatch { num_rows: u64, row_len: u64 },
+    /// A unary run is longer than the `max_quotient` of the decoder
+    RunTooLong,
+    /// The CRC-32 trailer doesn't match the stream
+    ChecksumMismatch,
+    /// The stream holds more values than the decoder allows
+    TooManyValues { max_values: u32 },
+    /// Allocating the decoded values failed
+    AllocationFailed,
+}
+
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DecodeError::MissingHeader => write!(f, "input is too short to contain a header"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported header version {}", version)
+            }
+            DecodeError::InvalidK(k) => write!(f, "invalid k {} in header", k),
+            DecodeError::InvalidBlockSize => write!(f, "block size of zero in header"),
+            DecodeError::InvalidMode(mode) => write!(f, "invalid block mode {:#010b}", mode),
+            DecodeError::UnexpectedEof => write!(f, "input ends in the middle of a value"),
+            DecodeError::Overflow => write!(f, "decoded value exceeds u32"),
+            DecodeError::BadPadding => write!(f, "invalid padding after the last value"),
+            DecodeError::RunTooLong => write!(f, "unary run exceeds the maximum quotient"),
+            DecodeError::ChecksumMismatch => write!(f, "checksum mismatch"),
+            DecodeError::TooManyValues { max_values } => {
+                write!(f, "stream holds more than {} values", max_values)
+            }
+            DecodeError::AllocationFailed => write!(f, "failed to allocate the decoded values"),
+            DecodeError::DimensionMismatch { num_rows, row_len } => {
+                write!(f, "matrix has {} rows of {} values", num_rows, row_len)
+            }
+            DecodeError::CountMismatch { expected, got } => {
+                write!(
+                    f,
+                    "expected {} values, but the stream ends after {}",
+                    expected, got
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+/// Decodes a stream written by `RiceCoder::encode_with_header`, reading `k` from the header.
+pub fn decode_with_header(input: &[u8]) -> Result<Vec<u32>, DecodeError> {
+    RiceView::try_from(input).map(RiceView::decode)
+}
+
+/// Encodes `values` with the `k` picked by `best_k_by_size` in the header format, returning the
+/// chosen `k` and the encoded bytes. Decode with `decode_best`.
+pub fn encode_best(values: &[u32]) -> (u8, Vec<u8>) {
+    let k = best_k_by_size(values);
+    let mut output = Vec::with_capacity(1 + encoded_len_bytes(values, k) as usize);
+    RiceCoder::new(k).encode_with_header(values, &mut output);
+    (k, output)
+}
+
+/// Encodes sorted document ids, e.g. a posting list, as deltas in the header format with the `k`
+/// picked by `best_k_by_size` for the deltas. Decode with `decode_docids`.
+///
+/// The deltas between neighboring ids are much smaller than the ids, so a small `k` fits them.
+/// Duplicate ids are kept as deltas of 0.
+///
+/// # Panics
+///
+/// Panics in debug builds if `sorted_docids` is not sorted.
+pub fn encode_docids(sorted_docids: &[u32]) -> Vec<u8> {
+    debug_assert!(
+        sorted_docids.windows(2).all(|pair| pair[0] <= pair[1]),
+        "encode_docids requires sorted docids"
+    );
+    let deltas: Vec<u32> = sorted_docids
+        .first()
+        .copied()
+        .into_iter()
+        .chain(sorted_docids.windows(2).map(|pair| pair[1] - pair[0]))
+        .collect();
+    let k = best_k_by_size(&deltas);
+    let mut output = Vec::with_capacity(1 + reserve_for(&deltas, k));
+    RiceCoder::new(k).encode_with_header(&deltas, &mut output);
+    output
+}
+
+/// Decodes document ids written by `encode_docids`, reading `k` from the header.
+pub fn decode_docids(input: &[u8]) -> Result<Vec<u32>, DecodeError> {
+    let view = RiceView::try_from(input)?;
+    let mut out = Vec::new();
+    RiceCoder::new(view.k()).decode_deltas_into(view.data(), &mut out, u32::MAX);
+    Ok(out)
+}
+
+/// Encodes and finalizes `values` with `k` into a boxed slice without excess capacity, e.g. for
+/// storing many immutable blocks. Decode with `RiceCoder::decode_into`.
+///
+/// # Panics
+///
+/// Panics if `k > 31`.
+pub fn encode_to_boxed(values: &[u32], k: u8) -> Box<[u8]> {
+    let mut output = Vec::with_capacity(reserve_for(values, k));
+    RiceCoder::new(k).encode_vals(values, &mut output);
+    output.into_boxed_slice()
+}
+
+/// Decodes a stream written by `encode_best`, reading `k` from the header.
+pub fn decode_best(input: &[u8]) -> Result<Vec<u32>, DecodeError> {
+    decode_with_header(input)
+}
+
+/// Decodes up to `num_vals` values from each of two independent streams, given as encoded bytes
+/// and their `k`, in lockstep.
+///
+/// Interleaving the two independent dependency chains lets the CPU overlap them, which is faster
+/// than two sequential `RiceCoder::decode_into` calls, e.g. for two columns of a column store.
+///
+/// Returns the number of bytes read from each stream
+///
+/// # Panics
+///
+/// Panics if a `k` is larger than 31.
+pub fn decode_pair_into(
+    a: (&[u8], u8),
+    b: (&[u8], u8),
+    out_a: &mut Vec<u32>,
+    out_b: &mut Vec<u32>,
+    num_vals: u32,
+) -> (usize, usize) {
+    let (coder_a, coder_b) = (RiceCoder::new(a.1), RiceCoder::new(b.1));
+    let (mut reader_a, mut reader_b) = (BitReader::new(a.0), BitReader::new(b.0));
+    let mut remaining_a = num_vals;
+    let mut remaining_b = num_vals;
+    while remaining_a > 0 && remaining_b > 0 {
+        match (
+            coder_a.decode_value(&mut reader_a),
+            coder_b.decode_value(&mut reader_b),
+        ) {
+            (Some(value_a), Some(value_b)) => {
+                out_a.push(value_a);
+                out_b.push(value_b);
+                remaining_a -= 1;
+                remaining_b -= 1;
+            }
+            (Some(value_a), None) => {
+                out_a.push(value_a);
+                remaining_a -= 1;
+                remaining_b = 0;
+            }
+            (None, Some(value_b)) => {
+                out_b.push(value_b);
+                remaining_b -= 1;
+                remaining_a = 0;
+            }
+            (None, None) => break,
+        }
+    }
+    // One stream is done, finish the other alone
+    for (coder, reader, out, remaining) in [
+        (&coder_a, &mut reader_a, &mut *out_a, remaining_a),
+        (&coder_b, &mut reader_b, &mut *out_b, remaining_b),
+    ] {
+        for _ in 0..remaining {
+            match coder.decode_value(reader) {
+                Some(value) => out.push(value),
+                None => break,
+            }
+        }
+    }
+    (
+        core::cmp::min(reader_a.byte_pos() + 1, a.0.len()),
+        core::cmp::min(reader_b.byte_pos() + 1, b.0.len()),
+    )
+}
+
+/// Decodes several finalized segments, each given as `(input, k, num_vals)`, one after another
+/// into `out`, e.g. a posting list split across the segments of a distributed index.
+///
+/// The same as calling `RiceCoder::decode_into` per segment. A segment ending early doesn't stop
+/// the following ones.
+///
+/// Returns the number of values appended to `out`
+///
+/// # Panics
+///
+/// Panics if a `k` is larger than 31.
+pub fn decode_segments_into(segments: &[(&[u8], u8, u32)], out: &mut Vec<u32>) -> usize {
+    let start_len = out.len();
+    for &(input, k, num_vals) in segments {
+        RiceCoder::new(k).decode_into(input, out, num_vals);
+    }
+    out.len() - start_len
+}
+
+/// Finds the `k` a finalized stream without header was encoded with, given the original values,
+/// e.g. to recover a stream whose `k` got lost. Tries every `k` in `0..=31` and returns the first
+/// one that decodes `encoded` to exactly `original`, or `None` if there is none.
+///
+/// A `k` only matches if nothing but padding follows the last value, so a smaller `k` that
+/// happens to decode `original` from a prefix of the stream isn't picked. Several `k` can still
+/// match, e.g. every `k` for an empty `original`.
+pub fn detect_k(encoded: &[u8], original: &[u32]) -> Option<u8> {
+    // One more value than expected, to detect anything but padding after the last value
+    let num_values = u32::try_from(original.len().saturating_add(1)).unwrap_or(u32::MAX);
+    let mut decoded_values = Vec::with_capacity(original.len() + 1);
+    (0..=MAX_K).find(|&k| {
+        decoded_values.clear();
+        RiceCoder::new(k).decode_into(encoded, &mut decoded_values, num_values);
+        decoded_values == original
+    })
+}
+
+/// Maps a signed integer to an unsigned one, so that small magnitudes map to small values.
+/// `0, -1, 1, -2, 2, ...` becomes `0, 1, 2, 3, 4, ...`
+#[inline]
+pub fn zigzag_encode(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+/// Inverse of `zigzag_encode`
+#[inline]
+pub fn zigzag_decode(value: u32) -> i32 {
+    ((value >> 1) as i32) ^ -((value & 1) as i32)
+}
+
+/// Largest `k` supported by `RiceCoder`
+pub const MAX_K: u8 = 31;
+
+#[inline]
+#[track_caller]
+fn assert_valid_k(k: u8) {
+    assert!(k <= MAX_K, "k must be at most {}, got {}", MAX_K, k);
+}
+
+/// Statistics of an encode pass, returned by `RiceCoder::encode_vals_with_stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncodeStats {
+    /// Number of values encoded
+    pub num_values: usize,
+    /// Number of bits of the codes, excluding the padding written by `finalize`
+    pub total_bits: u64,
+    /// Longest unary run, i.e. the largest quotient, capped by the escape `q_max`
+    pub max_unary_run: u32,
+    /// Number of values with a quotient above `EncodeStats::LONG_QUOTIENT`
+    pub long_quotients: usize,
+}
+
+impl EncodeStats {
+    /// Quotients above this are counted as `long_quotients`, a unary run longer than a 32-bit
+    /// word hints at a too small `k`.
+    pub const LONG_QUOTIENT: u32 = 32;
+
+    /// Average number of bits per value, 0 if there are no values
+    pub fn bits_per_value(&self) -> f64 {
+        if self.num_values == 0 {
+            return 0.0;
+        }
+        self.total_bits as f64 / self.num_values as f64
+    }
+}
+
+/// Outcome of `RiceCoder::decode_into_result`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeResult {
+    /// Number of values appended to the output
+    pub values_decoded: usize,
+    /// Number of bytes, including a partially used last byte, occupied by the decoded values
+    pub bytes_consumed: usize,
+}
+
+/// How the unary quotient is terminated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum UnaryStyle {
+    /// `quotient` `1`s followed by a `0`
+    #[default]
+    ZeroTerminated,
+    /// Like `ZeroTerminated`, but a quotient of `max` is written as `max` `1`s without the `0`,
+    /// since the run can't be longer. Saves a bit for the largest quotient, but values with a
+    /// larger quotient can't be encoded.
+    ///
+    /// The `1`s padding written by `finalize` can decode as values, so decode with the exact
+    /// number of values.
+    RunLengthLimited { max: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RiceCoder {
+    k: u8,
+    escape: Option<u32>, // Unary length after which the raw value is written instead
+    unary: UnaryStyle,
+    max_quotient: Option<u32>, // Longest unary run accepted when decoding
+    bits: BitBuffer,
+}
+
+impl Default for RiceCoder {
+    /// A coder with `k = 0`, i.e. pure unary coding
+    fn default() -> Self {
+        RiceCoder::new(0)
+    }
+}
+
+impl RiceCoder {
+    /// Constructor to create a RiceCoder for the given `k`
+    ///
+    /// `k == 0` degenerates to pure unary coding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k > 31`, since the remainder has to fit into a `u32`.
+    pub fn new(k: u8) -> Self {
+        assert_valid_k(k);
+        RiceCoder {
+            k,
+            escape: None,
+            unary: UnaryStyle::ZeroTerminated,
+            max_quotient: None,
+            bits: BitBuffer::new(),
+        }
+    }
+
+    /// Constructor for limited Rice coding.
+    ///
+    /// Values with a quotient of at least `q_max` are written as `q_max` `1`s followed by the
+    /// raw 32-bit value, which bounds the cost of a single value to `q_max + 32` bits.
+    /// The decoder must be created with the same `k` and `q_max`.
+    pub fn with_escape(k: u8, q_max: u32) -> Self {
+        RiceCoder {
+            escape: Some(q_max),
+            ..RiceCoder::new(k)
+        }
+    }
+
+    /// Constructor for a coder packing bits into bytes in the given order, e.g.
+    /// `BitOrder::LsbFirst` to interoperate with formats packing the first bit into the least
+    /// significant bit of a byte.
+    ///
+    /// The stream must be decoded by a coder with the same order.
+    pub fn with_bit_order(k: u8, order: BitOrder) -> Self {
+        RiceCoder {
+            bits: BitBuffer::with_order(order),
+            ..RiceCoder::new(k)
+        }
+    }
+
+    /// Constructor for a coder storing the 32-bit words of the stream in the given byte order,
+    /// e.g. `ByteEndian::Little` for formats reading the stream as little-endian words. See
+    /// `ByteEndian` for how it combines with the bit order.
+    ///
+    /// With `ByteEndian::Little`, `finalize` pads the stream to a whole word and the number of
+    /// bytes read returned by the decode methods counts whole words. The stream must be decoded
+    /// by a coder with the same byte order.
+    pub fn with_byte_endian(k: u8, endian: ByteEndian) -> Self {
+        RiceCoder {
+            bits: BitBuffer::with_endian(BitOrder::MsbFirst, endian),
+            ..RiceCoder::new(k)
+        }
+    }
+
+    /// Constructor for a coder collecting the encoded bytes in a small internal array and copying
+    /// them into the output in bulk, instead of extending the output with every flushed word.
+    ///
+    /// The output is the same, but between `finalize` calls it lags behind by up to 64 bytes, so
+    /// the same output must be passed until `finalize`.
+    pub fn with_staging(k: u8) -> Self {
+        RiceCoder {
+            bits: BitBuffer::new().staged(),
+            ..RiceCoder::new(k)
+        }
+    }
+
+    /// Constructor for a coder terminating the unary quotient in the given style.
+    ///
+    /// The style is ignored with escape codes, which already limit the unary run. The stream
+    /// must be decoded by a coder with the same style.
+    ///
+    /// # Panics
+    ///
+    /// With `UnaryStyle::RunLengthLimited { max }`, `encode` panics on values whose quotient
+    /// `value >> k` exceeds `max`. Use `checked_encode` to reject them instead.
+    pub fn with_unary_style(k: u8, style: UnaryStyle) -> Self {
+        RiceCoder {
+            unary: style,
+            ..RiceCoder::new(k)
+        }
+    }
+
+    /// Constructor for a decoder rejecting quotients above `max_quotient` with
+    /// `DecodeError::RunTooLong`, e.g. for untrusted input, where a corrupt run of `1`s would
+    /// otherwise be read up to the largest quotient fitting a `u32`.
+    ///
+    /// The bound is ignored with escape codes, which already limit the unary run. Encoding is not
+    /// affected.
+    pub fn with_max_quotient(k: u8, max_quotient: u32) -> Self {
+        RiceCoder {
+            max_quotient: Some(max_quotient),
+            ..RiceCoder::new(k)
+        }
+    }
+
+    /// The number of remainder bits
+    pub fn k(&self) -> u8 {
+        self.k
+    }
+
+    /// The order in which bits are packed into bytes
+    pub fn bit_order(&self) -> BitOrder {
+        self.bits.order()
+    }
+
+    /// The byte order of the 32-bit words of the stream
+    pub fn byte_endian(&self) -> ByteEndian {
+        self.bits.endian()
+    }
+
+    /// Whether encoded bytes are staged before being copied into the output, see
+    /// `with_staging`
+    pub fn is_staged(&self) -> bool {
+        self.bits.is_staged()
+    }
+
+    /// How the unary quotient is terminated
+    pub fn unary_style(&self) -> UnaryStyle {
+        self.unary
+    }
+
+    /// The largest quotient to decode, given the largest one fitting the output type
+    #[inline]
+    fn quotient_limit(&self, max_quotient: u32) -> u32 {
+        match self.max_quotient {
+            Some(max) if self.escape.is_none() => core::cmp::min(max, max_quotient),
+            _ => max_quotient,
+        }
+    }
+
+    /// The longest unary run to read, given the largest quotient fitting the output type
+    #[inline]
+    fn unary_limit(&self, max_quotient: u32) -> u32 {
+        match (self.escape, self.unary) {
+            (Some(q_max), _) => q_max,
+            (None, UnaryStyle::ZeroTerminated) => max_quotient.saturating_add(1),
+            (None, UnaryStyle::RunLengthLimited { max }) => {
+                core::cmp::min(max, max_quotient.saturating_add(1))
+            }
+        }
+    }
+
+    /// Creates a reader over `input` with the bit order and byte order of the coder
+    #[inline]
+    fn reader<'a>(&self, input: &'a [u8]) -> BitReader<'a> {
+        BitReader::with_endian(input, self.bit_order(), self.byte_endian())
+    }
+
+    /// Discards any buffered bits, so the coder can start a new independent stream.
+    pub fn reset(&mut self) {
+        self.bits = self.bits.cleared();
+    }
+
+    /// Like `reset`, but also switches to a new `k`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k > 31`.
+    pub fn reset_with_k(&mut self, k: u8) {
+        assert_valid_k(k);
+        self.reset();
+        self.k = k;
+    }
+
+    /// Encodes all values and finalizes the stream.
+    ///
+    /// Since this finalizes, the coder can directly be reused for the next stream. Callers using
+    /// `encode` directly must `finalize` or `reset` between streams.
+    pub fn encode_vals(&mut self, values: &[u32], output: &mut Vec<u8>) {
+        self.encode_vals_no_finalize(values, output);
+        self.finalize(output);
+    }
+
+    /// Like `encode_vals`, but returns the range of `output` the finalized block occupies, e.g. to
+    /// build an offset index over several blocks appended to one buffer. Decode a block with
+    /// `decode_into` on `&output[range]`.
+    ///
+    /// The coder must not have buffered bits of a stream in progress, which would end up in the
+    /// block.
+    pub fn encode_vals_at(&mut self, values: &[u32], output: &mut Vec<u8>) -> Range<usize> {
+        let start = output.len();
+        self.encode_vals(values, output);
+        start..output.len()
+    }
+
+    /// Encodes and finalizes the values like `encode_vals`, taking ownership of them, e.g. for
+    /// bulk jobs that no longer need the plain values.
+    ///
+    /// The input can't be reused for the output since the element sizes differ, but it is freed
+    /// right after encoding, before the output is shrunk to fit. At the peak, both the input and
+    /// the output are allocated.
+    pub fn encode_vals_consuming(mut self, values: Vec<u32>) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.encode_vals(&values, &mut output);
+        drop(values);
+        output.shrink_to_fit();
+        output
+    }
+
+    /// Returns the number of bytes `encode_vals_no_finalize` reserves for `values`: the plain Rice
+    /// size computed by `reserve_for`, capped at `q_max + 32` bits per value with escape codes,
+    /// the most an escaped value takes, and rounded up to whole words for `ByteEndian::Little`.
+    fn reserve_len(&self, values: &[u32]) -> usize {
+        let mut num_bytes = encoded_len_bytes(values, self.k);
+        if let Some(q_max) = self.escape {
+            let escaped_bits = values.len() as u64 * (q_max as u64 + 32);
+            num_bytes = core::cmp::min(num_bytes, escaped_bits / 8 + 1);
+        }
+        if self.byte_endian() == ByteEndian::Little {
+            num_bytes = num_bytes.next_multiple_of(4);
+        }
+        usize::try_from(num_bytes).unwrap_or(usize::MAX)
+    }
+
+    /// Encodes all values like `encode_vals`, but without finalizing, so several slices can be
+    /// encoded into one stream.
+    ///
+    /// `finalize` must be called exactly once after the last slice, to flush the buffered bits
+    /// and write the padding.
+    pub fn encode_vals_no_finalize(&mut self, values: &[u32], output: &mut Vec<u8>) {
+        // Only a hint, the output still grows while encoding if the reservation fails
+        let _ = output.try_reserve(self.reserve_len(values));
+        #[cfg(feature = "simd")]
+        let values = {
+            let mut batches = values.chunks_exact(simd::BATCH_SIZE);
+            for batch in &mut batches {
+                let batch: &[u32; simd::BATCH_SIZE] = batch.try_into().unwrap();
+                let (quotients, remainders) = simd::split_batch(batch, self.k);
+                for i in 0..simd::BATCH_SIZE {
+                    self.encode_parts(batch[i], quotients[i], remainders[i], output);
+                }
+            }
+            batches.remainder()
+        };
+        for value in values {
+            self.encode(*value, output);
+        }
+    }
+
+    /// Like `encode_vals`, but also collects statistics of the realized encoding in the same pass,
+    /// e.g. to check whether `k` causes long unary runs.
+    pub fn encode_vals_with_stats(&mut self, values: &[u32], output: &mut Vec<u8>) -> EncodeStats {
+        let start_bits = self.bits.bits_written();
+        let max_quotient = self.escape.unwrap_or(u32::MAX);
+        let mut stats = EncodeStats {
+            num_values: values.len(),
+            ..EncodeStats::default()
+        };
+        for value in values {
+            let quotient = core::cmp::min(*value >> self.k, max_quotient);
+            stats.max_unary_run = core::cmp::max(stats.max_unary_run, quotient);
+            stats.long_quotients += (quotient > EncodeStats::LONG_QUOTIENT) as usize;
+            self.encode(*value, output);
+        }
+        stats.total_bits = self.bits.bits_written() - start_bits;
+        self.finalize(output);
+        stats
+    }
+
+    /// Encodes all values of an iterator and finalizes the stream.
+    ///
+    /// Produces the same output as `encode_vals` without collecting the values first.
+    pub fn encode_iter<I: IntoIterator<Item = u32>>(&mut self, values: I, output: &mut Vec<u8>) {
+        for value in values {
+            self.encode(value, output);
+        }
+        self.finalize(output);
+    }
+
+    /// Encodes the values prefixed with their count as LEB128 varint, so the stream is
+    /// self-terminating. Decode with `decode_counted_into`.
+    ///
+    /// Counted blocks can be concatenated in one buffer and decoded one after another.
+    pub fn encode_vals_counted(&mut self, values: &[u32], output: &mut Vec<u8>) {
+        write_varint(values.len() as u64, output);
+        self.encode_vals(values, output);
+    }
+
+    /// Concatenates finalized, byte aligned blocks without re-encoding, e.g. to merge index
+    /// segments. `counts[i]` is the number of values in `blocks[i]`.
+    ///
+    /// Each block is prefixed with its count, i.e. the result is the same as calling
+    /// `encode_vals_counted` per block. Decode the blocks one after another with
+    /// `decode_counted_into`, or starting at the returned byte offsets of the blocks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `blocks` and `counts` have different lengths.
+    pub fn concat_aligned<B: AsRef<[u8]>>(blocks: &[B], counts: &[u32]) -> (Vec<u8>, Vec<usize>) {
+        assert_eq!(blocks.len(), counts.len(), "one count per block required");
+        let total_len: usize = blocks.iter().map(|block| block.as_ref().len() + 5).sum();
+        let mut output = Vec::with_capacity(total_len);
+        let mut offsets = Vec::with_capacity(blocks.len());
+        for (block, count) in blocks.iter().zip(counts) {
+            offsets.push(output.len());
+            write_varint(*count as u64, &mut output);
+            output.extend_from_slice(block.as_ref());
+        }
+        (output, offsets)
+    }
+
+    /// Encodes the values either plain or run-length encoded, whichever is smaller, e.g. for
+    /// streams with many repeated values. Decode with `decode_rle_into`.
+    ///
+    /// The stream starts with a mode byte. For mode `0` a block as written by
+    /// `encode_vals_counted` follows. For mode `1` the values are collapsed into runs of equal
+    /// values: the number of runs as LEB128 varint, the `k` for the run values, the `k` for the
+    /// run lengths minus one, followed by a single stream alternating the value and the length of
+    /// each run. Both `k` are picked by `best_k_by_size`.
+    pub fn encode_rle(&mut self, values: &[u32], output: &mut Vec<u8>) {
+        let mut run_values = Vec::new();
+        let mut run_lengths: Vec<u32> = Vec::new();
+        for &value in values {
+            match (run_values.last(), run_lengths.last_mut()) {
+                (Some(&last), Some(length)) if last == value && *length < u32::MAX => *length += 1,
+                _ => {
+                    run_values.push(value);
+                    run_lengths.push(0);
+                }
+            }
+        }
+        let k_values = best_k_by_size(&run_values);
+        let k_runs = best_k_by_size(&run_lengths);
+        let rle_bits = encoded_len_bits(&run_values, k_values)
+            + encoded_len_bits(&run_lengths, k_runs)
+            + varint_len(run_values.len() as u64) as u64 * 8
+            + 16;
+        let plain_bits =
+            encoded_len_bits(values, self.k) + varint_len(values.len() as u64) as u64 * 8;
+        if rle_bits / 8 >= plain_bits / 8 {
+            output.push(0);
+            self.encode_vals_counted(values, output);
+            return;
+        }
+
+        output.push(1);
+        write_varint(run_values.len() as u64, output);
+        output.extend_from_slice(&[k_values, k_runs]);
+        let k = self.k;
+        for (value, length) in run_values.iter().zip(&run_lengths) {
+            self.k = k_values;
+            self.encode(*value, output);
+            self.k = k_runs;
+            self.encode(*length, output);
+        }
+        self.k = k;
+        self.finalize(output);
+    }
+
+    /// Encodes the values prefixed with a single header byte, so the stream can be decoded with
+    /// `decode_with_header` without knowing `k`.
+    ///
+    /// The header stores the format version in the upper 3 bits and `k` in the lower 5 bits.
+    pub fn encode_with_header(&mut self, values: &[u32], output: &mut Vec<u8>) {
+        output.push((HEADER_VERSION << 5) | self.k);
+        self.encode_vals(values, output);
+    }
+
+    /// Encodes the values with `offset` subtracted, which shrinks the quotients of values clustered
+    /// around a large minimum. Decode with `decode_with_offset_into`.
+    ///
+    /// The offset is stored as LEB128 varint in front of the Rice codes. Pass the minimum of the
+    /// values.
+    ///
+    /// Sorted values are better served by delta coding, where the only large delta is the first
+    /// value. Pair both by subtracting `sorted_values[0]` before `encode_deltas` and storing it
+    /// alongside, so the first delta becomes 0.
+    ///
+    /// # Data loss
+    ///
+    /// Values below `offset` saturate at 0 and silently decode as `offset`. Debug builds panic on
+    /// them, use `checked_encode_with_offset` to reject them instead.
+    pub fn encode_with_offset(&mut self, values: &[u32], offset: u32, output: &mut Vec<u8>) {
+        write_varint(offset as u64, output);
+        for &value in values {
+            debug_assert!(
+                value >= offset,
+                "value {} is below the offset {}",
+                value,
+                offset
+            );
+            self.encode(value.saturating_sub(offset), output);
+        }
+        self.finalize(output);
+    }
+
+    /// Like `encode_with_offset`, but returns `EncodeError::BelowOffset` for the first value below
+    /// `offset` instead of losing it. Nothing is written to `output` on error.
+    pub fn checked_encode_with_offset(
+        &mut self,
+        values: &[u32],
+        offset: u32,
+        output: &mut Vec<u8>,
+    ) -> Result<(), EncodeError> {
+        if let Some(&value) = values.iter().find(|&&value| value < offset) {
+            return Err(EncodeError::BelowOffset { value, offset });
+        }
+        self.encode_with_offset(values, offset, output);
+        Ok(())
+    }
+
+    /// Encodes a matrix row-major with a shared `k`, e.g. small residual blocks. Decode with
+    /// `decode_matrix_into`.
+    ///
+    /// Layout: a byte with `k`, the number of rows and the row length as LEB128 varints, followed
+    /// by the finalized Rice codes of all rows. The coder is left with `k`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k > 31` or the rows have different lengths.
+    pub fn encode_matrix(&mut self, rows: &[&[u32]], k: u8, output: &mut Vec<u8>) {
+        let row_len = rows.first().map_or(0, |row| row.len());
+        assert!(
+            rows.iter().all(|row| row.len() == row_len),
+            "all rows must have the same length"
+        );
+        self.reset_with_k(k);
+        output.push(k);
+        write_varint(rows.len() as u64, output);
+        write_varint(row_len as u64, output);
+        for row in rows {
+            self.encode_vals_no_finalize(row, output);
+        }
+        self.finalize(output);
+    }
+
+    /// Encodes the values in blocks of `block_size` values, each with its own `k` picked by
+    /// `best_k_by_size`. Decode with `decode_adaptive`.
+    ///
+    /// Layout: varint `block_size`, varint number of values, then per block a byte with `k`
+    /// followed by the finalized Rice codes of the block. The coder is left with the `k` of the
+    /// last block.
+    pub fn encode_adaptive(&mut self, values: &[u32], block_size: usize, output: &mut Vec<u8>) {
+        assert!(block_size > 0, "block_size must be positive");
+        write_varint(block_size as u64, output);
+        write_varint(values.len() as u64, output);
+        for block in values.chunks(block_size) {
+            let k = best_k_by_size(block);
+            output.push(k);
+            self.reset_with_k(k);
+            self.encode_vals(block, output);
+        }
+    }
+
+    /// Decodes a stream written by `encode_adaptive`, switching `k` per block. The escape
+    /// setting of this coder is used for all blocks.
+    ///
+    /// Every block holds `block_size` values, except the last one, whose length is what remains
+    /// of the stored number of values.
+    ///
+    /// Returns `DecodeError::UnexpectedEof` if a block ends before all of its values are decoded.
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_adaptive(&self, input: &[u8], out: &mut Vec<u32>) -> Result<usize, DecodeError> {
+        let mut pos = 0;
+        let block_size = read_varint(input, &mut pos).ok_or(DecodeError::MissingHeader)?;
+        let num_values = read_varint(input, &mut pos).ok_or(DecodeError::MissingHeader)?;
+        if block_size == 0 {
+            return Err(DecodeError::InvalidBlockSize);
+        }
+        let mut remaining = num_values;
+        while remaining > 0 {
+            let k = *input.get(pos).ok_or(DecodeError::MissingHeader)?;
+            if k > MAX_K {
+                return Err(DecodeError::InvalidK(k));
+            }
+            pos += 1;
+            let block_len = core::cmp::min(block_size, remaining);
+            let coder = RiceCoder { k, ..*self };
+            let start_len = out.len();
+            let num_block_values = u32::try_from(block_len).unwrap_or(u32::MAX);
+            pos += coder.decode_into(&input[pos..], out, num_block_values);
+            if ((out.len() - start_len) as u64) < block_len {
+                return Err(DecodeError::UnexpectedEof);
+            }
+            remaining -= block_len;
+        }
+        Ok(pos)
+    }
+
+    /// Encodes a non-decreasing sequence as the differences between successive values, the first
+    /// value as difference from 0. Decode with `decode_deltas_into`.
+    ///
+    /// For sorted data like posting lists the deltas are much smaller than the values.
+    pub fn encode_deltas(&mut self, sorted_values: &[u32], output: &mut Vec<u8>) {
+        debug_assert!(
+            sorted_values.windows(2).all(|pair| pair[0] <= pair[1]),
+            "encode_deltas requires non-decreasing values"
+        );
+        let mut previous = 0;
+        for value in sorted_values {
+            self.encode(value - previous, output);
+            previous = *value;
+        }
+        self.finalize(output);
+    }
+
+    /// Encodes signed values by zig-zag mapping them to unsigned values first.
+    /// Decode with `decode_signed_into`.
+    pub fn encode_signed_vals(&mut self, values: &[i32], output: &mut Vec<u8>) {
+        for value in values {
+            self.encode(zigzag_encode(*value), output);
+        }
+        self.finalize(output);
+    }
+
+    /// Rice encoding for a given integer, returning an error instead of encoding values whose
+    /// quotient `value >> k` exceeds `MAX_QUOTIENT`, or the `max` of
+    /// `UnaryStyle::RunLengthLimited`.
+    /// Need to call finalize at the end
+    pub fn checked_encode(&mut self, value: u32, output: &mut Vec<u8>) -> Result<(), EncodeError> {
+        if let (None, UnaryStyle::RunLengthLimited { max }) = (self.escape, self.unary) {
+            if value >> self.k > max {
+                return Err(EncodeError::RunTooLong { value, max });
+            }
+        }
+        if value > self.max_checked_value() {
+            return Err(EncodeError::QuotientTooLarge { value, k: self.k });
+        }
+        self.encode(value, output);
+        Ok(())
+    }
+
+    /// The largest value accepted by `checked_encode` for this coder's `k`
+    pub fn max_checked_value(&self) -> u32 {
+        let max = ((MAX_QUOTIENT as u64 + 1) << self.k) - 1;
+        core::cmp::min(max, u32::MAX as u64) as u32
+    }
+
+    /// The largest value that round-trips through this coder, every value up to it can be
+    /// encoded and decoded again.
+    ///
+    /// Escape codes store large values raw, so any `u32` round-trips. Otherwise the longest
+    /// unary run is bounded by the `max` of `UnaryStyle::RunLengthLimited`, which `encode`
+    /// can't exceed, and by `with_max_quotient`, above which decoding returns
+    /// `DecodeError::RunTooLong`. The largest value for a quotient limit `q` is
+    /// `((q + 1) << k) - 1`, capped at `u32::MAX`.
+    pub fn max_safe_value(&self) -> u32 {
+        if self.escape.is_some() {
+            return u32::MAX;
+        }
+        let run_limit = match self.unary {
+            UnaryStyle::RunLengthLimited { max } => max,
+            UnaryStyle::ZeroTerminated => u32::MAX,
+        };
+        let quotient_limit = core::cmp::min(run_limit, self.max_quotient.unwrap_or(u32::MAX));
+        let max = ((quotient_limit as u64 + 1) << self.k) - 1;
+        core::cmp::min(max, u32::MAX as u64) as u32
+    }
+
+    /// Rice encoding for a given integer
+    /// Need to call finalize at the end
+    ///
+    /// Appends to the stream in progress, e.g. to interleave encoding with other work,
+    /// `encode_vals` is the batch equivalent. Only completed bytes are written to `output`, the
+    /// remaining bits stay buffered in the coder. Keep passing the same `output` until `finalize`
+    /// writes them, the stream is incomplete without it. Call `finalize`, or `reset` to discard
+    /// the buffered bits, before starting another stream.
+    ///
+    /// Every value costs `(value >> k) + 1 + k` bits, so large values with a small `k` produce
+    /// long unary runs. Use `checked_encode` to reject such values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the coder uses `UnaryStyle::RunLengthLimited { max }` without escape codes and
+    /// the quotient `value >> k` exceeds `max`.
+    #[inline]
+    pub fn encode(&mut self, value: u32, output: &mut Vec<u8>) {
+        let quotient = value >> self.k; // value / 2^k
+
+        // `k <= 31` is checked on construction, so the shift can't overflow. The mask is empty
+        // for `k = 0`.
+        let remainder = value & ((1 << self.k) - 1); // value % 2^k
+        self.encode_parts(value, quotient, remainder, output);
+    }
+
+    /// Encodes a value already split into quotient and remainder
+    #[inline]
+    fn encode_parts(&mut self, value: u32, quotient: u32, remainder: u32, output: &mut Vec<u8>) {
+        if let Some(q_max) = self.escape {
+            if quotient >= q_max {
+                // Escape: `q_max` 1s without terminating `0`, followed by the raw value
+                self.bits.write_ones(q_max, output);
+                self.bits.write_bits(value, 32);
+                self.bits.flush(output);
+                return;
+            }
+        }
+
+        self.bits.write_ones(quotient, output);
+
+        // Write the final `0` after all 1s, unless the run length limit implies it
+        let terminated = match self.unary {
+            UnaryStyle::RunLengthLimited { max } if self.escape.is_none() => {
+                assert!(
+                    quotient <= max,
+                    "quotient {} exceeds the unary run length limit {}",
+                    quotient,
+                    max
+                );
+                quotient < max
+            }
+            _ => true,
+        };
+        if terminated {
+            self.bits.write_bits(0, 1);
+        }
+
+        // Write the remainder in binary form (k bits), nothing for pure unary codes
+        if self.k > 0 {
+            self.bits.write_bits(remainder, self.k);
+        }
+        self.bits.flush(output);
+    }
+
+    /// Finalize encoding by flushing any remaining bits in the buffer
+    /// We will pad the remaining bits with `1`s to signal the end of the stream.
+    pub fn finalize(&mut self, output: &mut Vec<u8>) {
+        self.bits.finalize(output);
+    }
+
+    /// Finalize encoding like `finalize`, but pad the last partial byte with `0`s instead of the
+    /// `1`s EOF marker.
+    ///
+    /// Returns the number of bits written since the last finalize, excluding the padding. Since
+    /// `0` padding can decode as values, the stream has to be decoded with `decode_bits_into`
+    /// and this bit count.
+    pub fn finalize_exact(&mut self, output: &mut Vec<u8>) -> u64 {
+        self.bits.finalize_exact(output)
+    }
+
+    /// Decodes a single value starting at the given position and advances the position.
+    ///
+    /// Returns `None` if the input ends before the value is complete, or the value doesn't fit
+    /// into a `u32`.
+    #[inline]
+    fn decode_value(&self, reader: &mut BitReader) -> Option<u32> {
+        self.try_decode_value(reader).ok()
+    }
+
+    /// Like `decode_value`, but tells apart `DecodeError::UnexpectedEof`,
+    /// `DecodeError::Overflow` and `DecodeError::RunTooLong`.
+    #[inline]
+    fn try_decode_value(&self, reader: &mut BitReader) -> Result<u32, DecodeError> {
+        // Decode unary quotient, a run of `1`s reaching the end of input is the EOF marker
+        let start = *reader;
+        let max_quotient = u32::MAX >> self.k;
+        let quotient_limit = self.quotient_limit(max_quotient);
+        let limit = self.unary_limit(quotient_limit);
+        let quotient = reader.read_unary(limit).ok_or(DecodeError::UnexpectedEof)?;
+
+        if self.escape == Some(quotient) {
+            // Escaped value, stored as raw 32 bits
+            return reader.read_bits(32).ok_or(DecodeError::UnexpectedEof);
+        }
+        if quotient > quotient_limit {
+            // Only corrupt input, or a stream of wider values, has a quotient with bits beyond
+            // u32 or above `max_quotient`. Stop before reading the rest of the run, unless it is
+            // the `1`s padding.
+            return Err(if start.at_padding() {
+                DecodeError::UnexpectedEof
+            } else if quotient > max_quotient {
+                DecodeError::Overflow
+            } else {
+                DecodeError::RunTooLong
+            });
+        }
+
+        if self.k == 0 {
+            return Ok(quotient);
+        }
+        // Decode the binary remainder
+        let remainder = reader.read_bits(self.k).ok_or(DecodeError::UnexpectedEof)?;
+        Ok((quotient << self.k) | remainder)
+    }
+
+    /// Decodes a single value at bit `bit_pos` of byte `byte_pos` and advances the cursors past
+    /// it, e.g. for random access via an external index of value offsets.
+    ///
+    /// `bit_pos` counts in the bit order of the coder, 0 is the first bit of a byte.
+    ///
+    /// Returns `None`, without advancing the cursors, if the input ends before the value is
+    /// complete.
+    #[inline]
+    pub fn decode_one(&self, input: &[u8], byte_pos: &mut usize, bit_pos: &mut u8) -> Option<u32> {
+        let mut reader = BitReader::with_position(input, *byte_pos, *bit_pos, self.bit_order())
+            .endian(self.byte_endian());
+        let value = self.decode_value(&mut reader)?;
+        *byte_pos = reader.byte_pos();
+        *bit_pos = reader.bit_pos();
+        Some(value)
+    }
+
+    /// Returns an iterator lazily decoding up to `num_values` values from `input`
+    pub fn iter_decode<'a>(&'a self, input: &'a [u8], num_values: u32) -> RiceDecoder<'a> {
+        RiceDecoder::new(self, input, num_values)
+    }
+
+    /// Returns an iterator lazily decoding up to `num_values` values written by `encode_deltas`,
+    /// yielding the reconstructed values instead of the deltas
+    pub fn iter_decode_deltas<'a>(
+        &'a self,
+        input: &'a [u8],
+        num_values: u32,
+    ) -> PrefixSumDecoder<'a> {
+        PrefixSumDecoder::new(self, input, num_values)
+    }
+
+    /// Rice decoding for all integers in a byte stream
+    ///
+    /// Decodes until the input is exhausted, relying on the `1`s padding written by `finalize`
+    /// to detect the end of the stream.
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_all_into(&self, input: &[u8], out: &mut Vec<u32>) -> usize {
+        self.decode_into(input, out, u32::MAX)
+    }
+
+    /// Rice decoding for `num_values` integers from a byte stream
+    ///
+    /// Stops after `num_values` values have been appended to `out`, or earlier if the input is
+    /// exhausted.
+    ///
+    /// Returns the number of bytes read, which is 0 for an empty input. Input consisting only of
+    /// padding decodes to no values, all of it counting as read.
+    pub fn decode_into(&self, input: &[u8], out: &mut Vec<u32>, num_values: u32) -> usize {
+        self.decode_with(input, num_values, |value| out.push(value))
+    }
+
+    /// Like `decode_into`, but passes each value to `sink` instead of appending it to a `Vec`,
+    /// e.g. to insert the values into a set or a buffer of another type.
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_to_sink(
+        &self,
+        input: &[u8],
+        num_values: u32,
+        sink: &mut dyn FnMut(u32),
+    ) -> usize {
+        self.decode_with(input, num_values, sink)
+    }
+
+    /// Decodes up to `num_values` values like `decode_into`, but returns their quotients and
+    /// remainders separately, e.g. to check whether large quotients point to a bad `k`.
+    ///
+    /// Each value is `(quotient << k) + remainder`. For an escaped value, the quotient is the one
+    /// the value would have without the escape code.
+    pub fn decode_components(&self, input: &[u8], num_values: u32) -> (Vec<u32>, Vec<u32>) {
+        let mask = (1u32 << self.k) - 1;
+        let mut quotients = Vec::new();
+        let mut remainders = Vec::new();
+        self.decode_with(input, num_values, |value| {
+            quotients.push(value >> self.k);
+            remainders.push(value & mask);
+        });
+        (quotients, remainders)
+    }
+
+    /// Decodes up to `num_values` values, passing them to `sink`. The primitive of `decode_into`
+    /// and `decode_to_sink`.
+    #[inline]
+    fn decode_with(&self, input: &[u8], num_values: u32, mut sink: impl FnMut(u32)) -> usize {
+        let mut reader = self.reader(input);
+        let plain = self.escape.is_none()
+            && self.unary == UnaryStyle::ZeroTerminated
+            && self.max_quotient.is_none();
+
+        if self.k == 0 && plain {
+            // Pure unary, decoded a word at a time
+            let mut remaining = num_values as usize;
+            while remaining > 0 {
+                let num_runs = reader.read_unary_runs(remaining, &mut sink);
+                remaining -= num_runs;
+                if num_runs == 0 {
+                    // A run longer than the word, or less than 8 bytes left
+                    match self.decode_value(&mut reader) {
+                        Some(value) => sink(value),
+                        None => break,
+                    }
+                    remaining -= 1;
+                }
+            }
+            return reader.bytes_read();
+        }
+
+        if self.k <= table::TABLE_MAX_K && plain {
+            let decode_value = |reader: &mut BitReader| self.decode_value(reader);
+            table::decode_with_table(self.k, &mut reader, num_values as usize, decode_value, sink);
+            return reader.bytes_read();
+        }
+
+        for _ in 0..num_values {
+            match self.decode_value(&mut reader) {
+                Some(value) => sink(value),
+                None => break, // Not enough bits to complete the number, hit EOF marker
+            }
+        }
+        reader.bytes_read()
+    }
+
+    /// Like `decode_into`, but decodes directly into another integer type, e.g. `Vec<u64>` or
+    /// `Vec<usize>`, without a conversion pass.
+    ///
+    /// A wider type also accepts quotients beyond `u32`, e.g. from a `RiceCoder64` stream with
+    /// `k <= 31`. `decode_into` stays the faster choice for `u32`.
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_into_as<T: RiceInt>(
+        &self,
+        input: &[u8],
+        out: &mut Vec<T>,
+        num_values: u32,
+    ) -> usize {
+        let end_len = out.len().saturating_add(num_values as usize);
+        let mut reader = self.reader(input);
+        let max_quotient = self.quotient_limit(T::max_quotient(self.k));
+        let limit = self.unary_limit(max_quotient);
+
+        while out.len() < end_len {
+            let Some(quotient) = reader.read_unary(limit) else {
+                break; // Hit EOF marker
+            };
+            let value = if self.escape == Some(quotient) {
+                // Escaped value, stored as raw 32 bits
+                reader.read_bits(32).map(|value| T::from_parts(value, 0, 0))
+            } else if quotient > max_quotient {
+                None // Corrupt input
+            } else {
+                let remainder = reader.read_bits(self.k);
+                remainder.map(|remainder| T::from_parts(quotient, remainder, self.k))
+            };
+            match value {
+                Some(value) => out.push(value),
+                None => break, // Not enough bits to complete the number, hit EOF marker
+            }
+        }
+        reader.bytes_read()
+    }
+
+    /// Rice decoding of `take` integers after skipping the first `skip` ones, e.g. for paginated
+    /// access. The skipped values still have to be parsed, but are not collected.
+    ///
+    /// Stops early if the input is exhausted.
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_range_into(
+        &self,
+        input: &[u8],
+        skip: u32,
+        take: u32,
+        out: &mut Vec<u32>,
+    ) -> usize {
+        let mut reader = self.reader(input);
+        for _ in 0..skip {
+            if self.decode_value(&mut reader).is_none() {
+                return reader.bytes_read(); // Hit EOF marker
+            }
+        }
+        for _ in 0..take {
+            match self.decode_value(&mut reader) {
+                Some(value) => out.push(value),
+                None => break, // Hit EOF marker
+            }
+        }
+        reader.bytes_read()
+    }
+
+    /// Re-encodes up to `num_vals` values of a finalized stream with `new_k`, one value at a time
+    /// without materializing the decoded values.
+    ///
+    /// The output is the same as `decode_into` followed by `encode_vals` with a coder for
+    /// `new_k` with the same escape and bit order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_k > 31`.
+    pub fn transcode(&self, input: &[u8], num_vals: u32, new_k: u8) -> Vec<u8> {
+        assert_valid_k(new_k);
+        let mut encoder = RiceCoder {
+            k: new_k,
+            bits: self.bits.cleared(),
+            ..*self
+        };
+        let mut output = Vec::new();
+        let mut reader = self.reader(input);
+        for _ in 0..num_vals {
+            match self.decode_value(&mut reader) {
+                Some(value) => encoder.encode(value, &mut output),
+                None => break, // Hit EOF marker
+            }
+        }
+        encoder.finalize(&mut output);
+        output
+    }
+
+    /// Rice decoding of all values in chunks of up to `chunk_size` values, so memory stays
+    /// bounded regardless of the stream length.
+    ///
+    /// `f` is called for each chunk with a scratch buffer that is reused across calls. Decoding
+    /// stops at the first value that can't be completed, i.e. the EOF marker.
+    ///
+    /// Returns the number of bytes read
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size == 0`.
+    pub fn decode_chunks(
+        &self,
+        input: &[u8],
+        chunk_size: usize,
+        mut f: impl FnMut(&[u32]),
+    ) -> usize {
+        assert!(chunk_size > 0, "chunk_size must be positive");
+        let mut reader = self.reader(input);
+        let mut chunk = Vec::with_capacity(chunk_size);
+        loop {
+            while chunk.len() < chunk_size {
+                match self.decode_value(&mut reader) {
+                    Some(value) => chunk.push(value),
+                    None => break,
+                }
+            }
+            if !chunk.is_empty() {
+                f(&chunk);
+            }
+            if chunk.len() < chunk_size {
+                return reader.bytes_read();
+            }
+            chunk.clear();
+        }
+    }
+
+    /// Rice decoding of all values within the first `total_bits` bits, e.g. a stream written with
+    /// `finalize_exact`. Any bits after `total_bits`, like the padding of the last byte, are
+    /// ignored: a value ending after `total_bits` is not decoded.
+    ///
+    /// Returns the number of bytes read, which covers whole words for `ByteEndian::Little`
+    pub fn decode_bits_into(&self, input: &[u8], total_bits: u64, out: &mut Vec<u32>) -> usize {
+        let unit_bits = match self.byte_endian() {
+            ByteEndian::Big => 8,
+            ByteEndian::Little => 32,
+        };
+        let padded_bytes = total_bits.div_ceil(unit_bits) * (unit_bits / 8);
+        let num_bytes = core::cmp::min(padded_bytes, input.len() as u64) as usize;
+        let mut reader = self.reader(&input[..num_bytes]);
+        while reader.bits_read() < total_bits {
+            match self.decode_value(&mut reader) {
+                Some(value) if reader.bits_read() <= total_bits => out.push(value),
+                _ => break, // Input shorter than `total_bits`, or a value cut off by it
+            }
+        }
+        num_bytes
+    }
+
+    /// Rice decoding into a preallocated slice, so a buffer can be reused across blocks without
+    /// allocating.
+    ///
+    /// Stops when `out` is full or the input is exhausted. Returns the number of values written
+    pub fn decode_into_slice(&self, input: &[u8], out: &mut [u32]) -> usize {
+        let mut reader = self.reader(input);
+        for (num_written, slot) in out.iter_mut().enumerate() {
+            match self.decode_value(&mut reader) {
+                Some(value) => *slot = value,
+                None => return num_written, // Hit EOF marker
+            }
+        }
+        out.len()
+    }
+
+    /// Like `decode_into`, but returns `DecodeError::UnexpectedEof` if the input ends in the middle
+    /// of a value, e.g. because it was truncated, and `DecodeError::Overflow` if a value doesn't fit
+    /// into a `u32`, e.g. because the input is corrupt. A coder created with `with_max_quotient`
+    /// returns `DecodeError::RunTooLong` for longer unary runs.
+    ///
+    /// Ending before `num_values` values is not an error if only the `1`s padding written by
+    /// `finalize` is left.
+    ///
+    /// Returns the number of bytes read
+    pub fn try_decode_into(
+        &self,
+        input: &[u8],
+        out: &mut Vec<u32>,
+        num_values: u32,
+    ) -> Result<usize, DecodeError> {
+        let mut reader = self.reader(input);
+        self.try_decode_reader(&mut reader, out, num_values)?;
+        Ok(reader.bytes_read())
+    }
+
+    /// Like `try_decode_into`, but also verifies that the rest of the byte after the last value is
+    /// the `1`s padding written by `finalize`, returning `DecodeError::BadPadding` otherwise. This
+    /// catches corrupt or truncated streams that happen to decode.
+    ///
+    /// Only the padding is checked, so the stream may be followed by other data.
+    ///
+    /// Returns the number of bytes read, including the padding
+    pub fn try_decode_verified_into(
+        &self,
+        input: &[u8],
+        out: &mut Vec<u32>,
+        num_values: u32,
+    ) -> Result<usize, DecodeError> {
+        let mut reader = self.reader(input);
+        self.try_decode_reader(&mut reader, out, num_values)?;
+        if !reader.padding_valid() {
+            return Err(DecodeError::BadPadding);
+        }
+        Ok(reader.bytes_read())
+    }
+
+    /// Decodes up to `num_values` values, leaving `reader` after the last value
+    fn try_decode_reader(
+        &self,
+        reader: &mut BitReader,
+        out: &mut Vec<u32>,
+        num_values: u32,
+    ) -> Result<(), DecodeError> {
+        let end_len = out.len().saturating_add(num_values as usize);
+        while out.len() < end_len {
+            let start = *reader;
+            match self.try_decode_value(reader) {
+                Ok(value) => out.push(value),
+                Err(DecodeError::UnexpectedEof) if start.at_padding() => {
+                    *reader = start;
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes `num_vals` values of a finalized block embedded in a larger buffer, returning the
+    /// values and the rest of the buffer after the block, including its padding.
+    pub fn decode_framed<'a>(&self, input: &'a [u8], num_vals: u32) -> (Vec<u32>, &'a [u8]) {
+        let mut out = Vec::new();
+        let num_bytes = self.decode_into(input, &mut out, num_vals);
+        (out, &input[num_bytes..])
+    }
+
+    /// Strict variant of `try_decode_into`, which also returns `DecodeError::CountMismatch` if
+    /// the stream ends before `num_values` values, e.g. because of wrong external length
+    /// bookkeeping.
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_exact_into(
+        &self,
+        input: &[u8],
+        out: &mut Vec<u32>,
+        num_values: u32,
+    ) -> Result<usize, DecodeError> {
+        let start_len = out.len();
+        let num_bytes = self.try_decode_into(input, out, num_values)?;
+        let got = (out.len() - start_len) as u32;
+        if got < num_values {
+            return Err(DecodeError::CountMismatch {
+                expected: num_values,
+                got,
+            });
+        }
+        Ok(num_bytes)
+    }
+
+    /// Decodes a matrix written by `encode_matrix`, appending its rows to `out`. The `k` is read
+    /// from the header, the escape and bit order are the coder's.
+    ///
+    /// Returns `DecodeError::DimensionMismatch` if the header contains other dimensions than
+    /// `num_rows` rows of `row_len` values, and `DecodeError::UnexpectedEof` if the input ends
+    /// before all values.
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_matrix_into(
+        &self,
+        input: &[u8],
+        row_len: usize,
+        num_rows: usize,
+        out: &mut Vec<Vec<u32>>,
+    ) -> Result<usize, DecodeError> {
+        let (&k, body) = input.split_first().ok_or(DecodeError::MissingHeader)?;
+        if k > MAX_K {
+            return Err(DecodeError::InvalidK(k));
+        }
+        let mut pos = 0;
+        let header_rows = read_varint(body, &mut pos).ok_or(DecodeError::MissingHeader)?;
+        let header_row_len = read_varint(body, &mut pos).ok_or(DecodeError::MissingHeader)?;
+        if (header_rows, header_row_len) != (num_rows as u64, row_len as u64) {
+            return Err(DecodeError::DimensionMismatch {
+                num_rows: header_rows,
+                row_len: header_row_len,
+            });
+        }
+        let num_values = row_len
+            .checked_mul(num_rows)
+            .and_then(|num_values| u32::try_from(num_values).ok())
+            .ok_or(DecodeError::Overflow)?;
+
+        let coder = RiceCoder { k, ..*self };
+        let mut values = Vec::new();
+        let num_bytes = coder.try_decode_into(&body[pos..], &mut values, num_values)?;
+        if values.len() < num_values as usize {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        out.extend((0..num_rows).map(|row| values[row * row_len..][..row_len].to_vec()));
+        Ok(1 + pos + num_bytes)
+    }
+
+    /// Decodes a stream written by `encode_rle`, expanding the runs.
+    ///
+    /// A few bytes of run lengths can expand to billions of values, so streams holding more than
+    /// `max_values` values are rejected with `DecodeError::TooManyValues`, before expanding the
+    /// run exceeding the limit. Returns `DecodeError::AllocationFailed` if the expanded values
+    /// can't be allocated.
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_rle_into(
+        &self,
+        input: &[u8],
+        out: &mut Vec<u32>,
+        max_values: u32,
+    ) -> Result<usize, DecodeError> {
+        let too_many = DecodeError::TooManyValues { max_values };
+        let (&mode, body) = input.split_first().ok_or(DecodeError::MissingHeader)?;
+        match mode {
+            0 => {
+                let num_values = read_varint(body, &mut 0).ok_or(DecodeError::MissingHeader)?;
+                if num_values > max_values as u64 {
+                    return Err(too_many);
+                }
+                return Ok(1 + self.decode_counted_into(body, out)?);
+            }
+            1 => {}
+            _ => return Err(DecodeError::InvalidMode(mode)),
+        }
+        let mut pos = 0;
+        let num_runs = read_varint(body, &mut pos).ok_or(DecodeError::MissingHeader)?;
+        let [k_values, k_runs] = *body
+            .get(pos..pos + 2)
+            .and_then(|ks| <&[u8; 2]>::try_from(ks).ok())
+            .ok_or(DecodeError::MissingHeader)?;
+        for k in [k_values, k_runs] {
+            if k > MAX_K {
+                return Err(DecodeError::InvalidK(k));
+            }
+        }
+        pos += 2;
+
+        let value_coder = RiceCoder {
+            k: k_values,
+            ..*self
+        };
+        let run_coder = RiceCoder { k: k_runs, ..*self };
+        let mut reader = self.reader(&body[pos..]);
+        let mut remaining = max_values as u64;
+        for _ in 0..num_runs {
+            let value = value_coder.try_decode_value(&mut reader)?;
+            let length = run_coder.try_decode_value(&mut reader)? as u64 + 1;
+            remaining = remaining.checked_sub(length).ok_or(too_many)?;
+            out.try_reserve(length as usize)
+                .map_err(|_| DecodeError::AllocationFailed)?;
+            out.extend(core::iter::repeat_n(value, length as usize));
+        }
+        Ok(1 + pos + reader.bytes_read())
+    }
+
+    /// Decodes a block written by `encode_vals_counted`.
+    ///
+    /// Returns `DecodeError::MissingHeader` if the count is missing and `DecodeError::Overflow` if
+    /// it doesn't fit into a `u32`.
+    ///
+    /// Returns the number of bytes of the block, which is where the next block starts.
+    pub fn decode_counted_into(
+        &self,
+        input: &[u8],
+        out: &mut Vec<u32>,
+    ) -> Result<usize, DecodeError> {
+        let mut pos = 0;
+        let num_values = read_varint(input, &mut pos).ok_or(DecodeError::MissingHeader)?;
+        let num_values = u32::try_from(num_values).map_err(|_| DecodeError::Overflow)?;
+        let start_len = out.len();
+        let num_bytes = self.try_decode_into(&input[pos..], out, num_values)?;
+        if out.len() - start_len < num_values as usize {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        Ok(pos + num_bytes)
+    }
+
+    /// Decodes up to `num_values` values written by `encode_with_offset`, adding the offset back.
+    ///
+    /// Returns `DecodeError::MissingHeader` if the offset is missing and `DecodeError::Overflow`
+    /// if the offset, or a value plus the offset, doesn't fit into a `u32`. On error, `out` is
+    /// left as it was.
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_with_offset_into(
+        &self,
+        input: &[u8],
+        out: &mut Vec<u32>,
+        num_values: u32,
+    ) -> Result<usize, DecodeError> {
+        let mut pos = 0;
+        let offset = read_varint(input, &mut pos).ok_or(DecodeError::MissingHeader)?;
+        let offset = u32::try_from(offset).map_err(|_| DecodeError::Overflow)?;
+        let start_len = out.len();
+        let num_bytes = self.decode_into(&input[pos..], out, num_values);
+        for i in start_len..out.len() {
+            let Some(value) = out[i].checked_add(offset) else {
+                out.truncate(start_len);
+                return Err(DecodeError::Overflow);
+            };
+            out[i] = value;
+        }
+        Ok(pos + num_bytes)
+    }
+
+    /// Like `decode_into`, but reports the number of values decoded and the exact number of bytes
+    /// occupied by them.
+    ///
+    /// `bytes_consumed` includes the last partially used byte but never the padding byte
+    /// `finalize` appends to streams ending on a byte boundary, so it never exceeds `input.len()`.
+    pub fn decode_into_result(
+        &self,
+        input: &[u8],
+        out: &mut Vec<u32>,
+        num_values: u32,
+    ) -> DecodeResult {
+        let start_len = out.len();
+        let end_len = start_len.saturating_add(num_values as usize);
+        let mut reader = self.reader(input);
+
+        while out.len() < end_len {
+            // Only advance past complete values
+            let mut next = reader;
+            match self.decode_value(&mut next) {
+                Some(value) => out.push(value),
+                None => break, // Not enough bits to complete the number, hit EOF marker
+            }
+            reader = next;
+        }
+        DecodeResult {
+            values_decoded: out.len() - start_len,
+            bytes_consumed: reader.bytes_touched(),
+        }
+    }
+
+    /// Decodes `num_values` values written by `encode_deltas`, reconstructing them with a prefix
+    /// sum over the deltas.
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_deltas_into(&self, input: &[u8], out: &mut Vec<u32>, num_values: u32) -> usize {
+        let start_len = out.len();
+        let num_bytes = self.decode_into(input, out, num_values);
+        let mut sum: u32 = 0;
+        for value in &mut out[start_len..] {
+            sum = sum.wrapping_add(*value);
+            *value = sum;
+        }
+        num_bytes
+    }
+
+    /// Returns whether `target` is among the first `num_values` values written by
+    /// `encode_deltas`, e.g. a docid in a posting list.
+    ///
+    /// Decodes the deltas only until their running sum reaches `target`, so lookups of small
+    /// targets stop early.
+    pub fn contains_sorted(&self, input: &[u8], num_values: u32, target: u32) -> bool {
+        self.iter_decode_deltas(input, num_values)
+            .find(|&value| value >= target)
+            == Some(target)
+    }
+
+    /// Decodes `num_values` signed values written by `encode_signed_vals`
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_signed_into(&self, input: &[u8], out: &mut Vec<i32>, num_values: u32) -> usize {
+        let mut unsigned = Vec::new();
+        let num_bytes = self.decode_into(input, &mut unsigned, num_values);
+        out.extend(unsigned.into_iter().map(zigzag_decode));
+        num_bytes
+    }
+}
+
+pub fn create_rice_coder(k: u8) -> RiceCoder {
+    RiceCoder::new(k)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_case_1() {
+        let original_values: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        test_rice_coding(3, &original_values);
+    }
+
+    #[test]
+    fn test_decode_into_result() {
+        let values: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        let mut coder = create_rice_coder(3);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&values, &mut encoded);
+        assert_eq!(encoded.len(), 11); // 83 bits
+
+        let mut decoded_values = Vec::new();
+        let result = coder.decode_into_result(&encoded, &mut decoded_values, values.len() as u32);
+        assert_eq!(
+            result,
+            DecodeResult {
+                values_decoded: 7,
+                bytes_consumed: 11
+            }
+        );
+        assert_eq!(decoded_values, values);
+
+        // First value is 37: 4 ones, a zero and 3 remainder bits fill exactly one byte
+        let mut decoded_values = Vec::new();
+        let result = coder.decode_into_result(&encoded, &mut decoded_values, 1);
+        assert_eq!(
+            result,
+            DecodeResult {
+                values_decoded: 1,
+                bytes_consumed: 1
+            }
+        );
+
+        // Asking for more values stops at the end of the stream without overshooting
+        let mut decoded_values = Vec::new();
+        let result = coder.decode_into_result(&encoded, &mut decoded_values, 100);
+        assert_eq!(result.values_decoded, 7);
+        assert_eq!(result.bytes_consumed, 11);
+    }
+
+    #[test]
+    fn test_calculate_optimal_k_small_values() {
+        let values = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        // rank 3.5 -> 4.5
+        let optimal_k = estimate_optimal_k(&values, 50.0);
+        assert_eq!(optimal_k, 3);
+
+        // rank 6.3 -> 7.3
+        let optimal_k_90 = estimate_optimal_k(&values, 90.0);
+        assert_eq!(optimal_k_90, 3);
+    }
+
+    #[test]
+    fn test_estimate_optimal_k_percentile_bounds() {
+        let values = vec![9, 1, 300, 2, 40];
+        // Minimum, median and maximum
+        assert_eq!(estimate_optimal_k(&values, 0.0), 1);
+        assert_eq!(estimate_optimal_k(&values, 50.0), 4);
+        assert_eq!(estimate_optimal_k(&values, 100.0), 9);
+        // Interpolated between 40 and 300: rank 3.25 -> 105
+        assert_eq!(estimate_optimal_k(&values, 81.25), 7);
+
+        // Out of range percentiles are clamped
+        assert_eq!(estimate_optimal_k(&values, -10.0), 1);
+        assert_eq!(estimate_optimal_k(&values, 250.0), 9);
+        assert_eq!(estimate_optimal_k(&values, f64::INFINITY), 9);
+
+        assert_eq!(estimate_optimal_k(&[], 50.0), 0);
+    }
+
+    #[test]
+    fn test_estimate_k_reservoir() {
+        for (name, values) in bench_datasets() {
+            for percentile in [50.0, 90.0] {
+                let exact = estimate_optimal_k(&values, percentile);
+                // The whole stream fits into the reservoir
+                let k = estimate_k_reservoir(values.iter().copied(), values.len(), percentile);
+                assert_eq!(k, exact, "{}", name);
+                // Reproducible
+                let k = estimate_k_reservoir(values.iter().copied(), 64, percentile);
+                let again = estimate_k_reservoir(values.iter().copied(), 64, percentile);
+                assert_eq!(k, again, "{}", name);
+            }
+        }
+
+        // A large stream of mostly small values
+        let values = (0..1_000_000u32).map(|i| if i % 100 == 0 { 1 << 20 } else { i % 16 });
+        assert_eq!(estimate_k_reservoir(values.clone(), 1000, 40.0), 3);
+        assert_eq!(estimate_k_reservoir(values, 1000, 100.0), 21);
+        assert_eq!(estimate_k_reservoir(core::iter::empty(), 10, 50.0), 0);
+    }
+
+    #[test]
+    fn test_estimate_optimal_k_hist() {
+        let values = [1, 2, 3, 4, 5, 6, 7, 8];
+        for percentile in [0.0, 50.0, 80.0, 90.0, 100.0, 150.0] {
+            assert_eq!(
+                estimate_optimal_k_hist(&values, percentile),
+                estimate_optimal_k(&values, percentile)
+            );
+        }
+        assert_eq!(estimate_optimal_k_hist(&[], 50.0), 0);
+        assert_eq!(estimate_optimal_k_hist(&[0, u32::MAX], 100.0), 32);
+        // Interpolated between 7 and 64, crossing two bit lengths: 7 + 0.5 * 57 = 35
+        assert_eq!(estimate_optimal_k_hist(&[64, 7], 50.0), 6);
+    }
+
+    proptest! {
+        #[test]
+        fn test_estimate_optimal_k_hist_matches_sorting(values in prop::collection::vec(prop_oneof![0u32..16, 0u32..=100_000, any::<u32>()], 0..100), percentile in 0.0f64..=100.0) {
+            prop_assert_eq!(
+                estimate_optimal_k_hist(&values, percentile),
+                estimate_optimal_k(&values, percentile)
+            );
+        }
+    }
+
+    #[test]
+    fn test_percentile_rank() {
+        assert_eq!(percentile_rank(1, 100.0), (0, 0, 0.0));
+        assert_eq!(percentile_rank(5, 50.0), (2, 3, 0.0));
+        assert_eq!(percentile_rank(5, 100.0), (4, 4, 0.0));
+        assert_eq!(percentile_rank(3, 25.0), (0, 1, 0.5));
+        // Lengths too large to allocate, where `len - 1` isn't exact in `f64`
+        let len = usize::MAX - 1;
+        assert_eq!(percentile_rank(len, 100.0).0, len - 1);
+        for len in [usize::MAX, usize::MAX - 1, usize::MAX / 3] {
+            for percentile in [0.0, 50.0, 99.999_999, 100.0] {
+                let (lower_index, upper_index, _) = percentile_rank(len, percentile);
+                assert!(lower_index <= upper_index && upper_index < len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_detect_k() {
+        let values = [3, 9, 4, 0, 17];
+        for k in [0, 2, 5, 31] {
+            let mut encoded = Vec::new();
+            create_rice_coder(k).encode_vals(&values, &mut encoded);
+            assert_eq!(detect_k(&encoded, &values), Some(k));
+        }
+        // k = 0 decodes the first value, but the remaining bits aren't padding
+        let mut encoded = Vec::new();
+        create_rice_coder(3).encode_vals(&[0], &mut encoded);
+        assert_eq!(encoded, [0b0000_1111]);
+        assert_eq!(detect_k(&encoded, &[0]), Some(3));
+
+        // The same bits decode to 1 with k = 4
+        assert_eq!(detect_k(&encoded, &[1]), Some(4));
+        assert_eq!(detect_k(&encoded, &[2]), None);
+        assert_eq!(detect_k(&[], &[1]), None);
+        assert_eq!(detect_k(&[0xFF], &[]), Some(0));
+    }
+
+    #[test]
+    fn test_k_size_table_convex_for_geometric() {
+        let mut rng = StdRng::from_seed([7; 32]);
+        let values: Vec<u32> = (0..5000)
+            .map(|_| {
+                let mut value = 0;
+                while rng.gen_range(0..200) != 0 {
+                    value += 1;
+                }
+                value
+            })
+            .collect();
+        let table = k_size_table(&values);
+        assert_eq!(table.len(), 32);
+        assert!(table.iter().enumerate().all(|(i, (k, _))| i == *k as usize));
+        // Non-decreasing differences, so there is a single minimum
+        let diffs: Vec<i64> = table
+            .windows(2)
+            .map(|pair| pair[1].1 as i64 - pair[0].1 as i64)
+            .collect();
+        assert!(diffs.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        let (min_k, min_len) = *table.iter().min_by_key(|(_, len)| *len).unwrap();
+        assert_eq!(best_k_by_size(&values), min_k);
+        assert_eq!(encoded_len_bits(&values, min_k), min_len);
+        assert!((6..=8).contains(&min_k), "k = {}", min_k);
+    }
+
+    #[test]
+    #[should_panic(expected = "NaN")]
+    fn test_estimate_optimal_k_nan() {
+        estimate_optimal_k(&[1, 2, 3], f64::NAN);
+    }
+
+    #[test]
+    fn print_test() {
+        for val in 1..12 {
+            print!("{:0>2} ", val);
+            print::<2>(val);
+        }
+        //print(2, 2);
+        //print(2, 3);
+        //print(4, 4);
+        //print(1, 40);
+        //print(1, 41);
+        //print(1, 42);
+    }
+
+    fn print<const K: u8>(val: u32) {
+        let mut coder = RiceCoder::new(K); // Example with k = 3
+
+        // Encoding
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode(val, &mut encoded);
+        coder.finalize(&mut encoded);
+        print_bits(&encoded);
+    }
+
+    fn print_bits(bytes: &[u8]) {
+        for byte in bytes.iter() {
+            // Print the binary representation of each byte, padded to 8 bits
+            print!("{:08b} ", byte);
+        }
+        println!(); // Newline after printing all bytes
+    }
+
+    // Property-based test for random values
+    proptest! {
+        #[test]
+        fn test_rice_coding_random_values(values in prop::collection::vec(0u32..=500_000, 1..20), k in 1u8..8) {
+        //fn test_rice_coding_random_values(values in prop::collection::vec(0u32..=500, 1..3), k in 1u8..8) {
+            let mut coder = create_rice_coder(k); // Create a RiceCoder with the given k value
+
+            // Encoding
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+
+            // Decoding
+            let mut decoded_values = Vec::new();
+            let num_bytes = coder.decode_into(&encoded, &mut decoded_values, values.len() as u32);
+            prop_assert_eq!(num_bytes, encoded.len());
+
+            // Assert that the decoded values match the original values
+            prop_assert_eq!(values, decoded_values);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_rice_coding_random_signed_values(values in prop::collection::vec(-250_000i32..=250_000, 1..20), k in 1u8..8) {
+            let mut coder = create_rice_coder(k);
+
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_signed_vals(&values, &mut encoded);
+
+            let mut decoded_values = Vec::new();
+            let num_bytes = coder.decode_signed_into(&encoded, &mut decoded_values, values.len() as u32);
+            prop_assert_eq!(num_bytes, encoded.len());
+            prop_assert_eq!(values, decoded_values);
+        }
+
+        #[test]
+        fn test_zigzag_roundtrip(value in any::<i32>()) {
+            prop_assert_eq!(zigzag_decode(zigzag_encode(value)), value);
+        }
+    }
+
+    #[test]
+    fn test_signed_extremes() {
+        assert_eq!(zigzag_encode(0), 0);
+        assert_eq!(zigzag_encode(-1), 1);
+        assert_eq!(zigzag_encode(1), 2);
+        assert_eq!(zigzag_encode(i32::MAX), u32::MAX - 1);
+        assert_eq!(zigzag_encode(i32::MIN), u32::MAX);
+
+        let values = [i32::MIN, i32::MAX, 0, -1];
+        let mut coder = create_rice_coder(28);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_signed_vals(&values, &mut encoded);
+        let mut decoded_values = Vec::new();
+        coder.decode_signed_into(&encoded, &mut decoded_values, values.len() as u32);
+        assert_eq!(decoded_values, values);
+    }
+
+    proptest! {
+        #[test]
+        fn test_encode_matches_encode_vals(values in prop::collection::vec(0u32..=500_000, 0..40), k in 0u8..16) {
+            let mut coder = create_rice_coder(k);
+            let mut encoded_per_value: Vec<u8> = Vec::new();
+            for value in &values {
+                coder.encode(*value, &mut encoded_per_value);
+            }
+            coder.finalize(&mut encoded_per_value);
+
+            let mut encoded: Vec<u8> = Vec::new();
+            create_rice_coder(k).encode_vals(&values, &mut encoded);
+            prop_assert_eq!(encoded_per_value, encoded);
+        }
+    }
+
+    #[test]
+    fn test_header_roundtrip() {
+        let values: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        let mut coder = create_rice_coder(5);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_with_header(&values, &mut encoded);
+        assert_eq!(encoded[0], (HEADER_VERSION << 5) | 5);
+        assert_eq!(decode_with_header(&encoded), Ok(values));
+    }
+
+    #[test]
+    fn test_docids_roundtrip() {
+        let docids: Vec<u32> = (0..255).map(|docid| docid * 10).collect();
+        let encoded = encode_docids(&docids);
+        // All gaps are 10, with the first id 0
+        let mut deltas = vec![10; 255];
+        deltas[0] = 0;
+        assert_eq!(encoded[0] & 0b1_1111, best_k_by_size(&deltas));
+        assert!(encoded.len() < encoded_len_bytes(&docids, best_k_by_size(&docids)) as usize);
+        assert_eq!(decode_docids(&encoded), Ok(docids));
+
+        let docids = [7, 7, 8, 1000, u32::MAX];
+        assert_eq!(decode_docids(&encode_docids(&docids)), Ok(docids.to_vec()));
+        assert_eq!(decode_docids(&encode_docids(&[])), Ok(vec![]));
+        assert_eq!(decode_docids(&[]), Err(DecodeError::MissingHeader));
+    }
+
+    #[test]
+    fn test_header_errors() {
+        assert_eq!(decode_with_header(&[]), Err(DecodeError::MissingHeader));
+        assert_eq!(
+            decode_with_header(&[(7 << 5) | 3, 0]),
+            Err(DecodeError::UnsupportedVersion(7))
+        );
+    }
+
+    #[test]
+    fn test_checked_encode() {
+        let mut coder = create_rice_coder(1);
+        assert_eq!(coder.max_checked_value(), 131_071);
+        let mut encoded: Vec<u8> = Vec::new();
+        assert_eq!(coder.checked_encode(131_071, &mut encoded), Ok(()));
+        assert_eq!(
+            coder.checked_encode(131_072, &mut encoded),
+            Err(EncodeError::QuotientTooLarge {
+                value: 131_072,
+                k: 1
+            })
+        );
+        coder.finalize(&mut encoded);
+        let mut decoded_values = Vec::new();
+        coder.decode_into(&encoded, &mut decoded_values, 1);
+        assert_eq!(decoded_values, [131_071]);
+
+        assert_eq!(create_rice_coder(16).max_checked_value(), u32::MAX);
+    }
+
+    #[test]
+    fn test_large_values_high_k() {
+        // Long unary tail combined with a large remainder must not overflow the bit buffer
+        test_rice_coding(26, &[1, 31 << 26, u32::MAX, 7]);
+        test_rice_coding(20, &[u32::MAX, 3, u32::MAX]);
+    }
+
+    #[test]
+    fn test_escape_zipf_tail() {
+        use rand::{prelude::Distribution, rngs::StdRng, SeedableRng};
+        let mut rng = StdRng::from_seed([3u8; 32]);
+        let zipf = zipf::ZipfDistribution::new(2_000_000, 1.1).unwrap();
+        let mut values: Vec<u32> = (0..1000).map(|_| zipf.sample(&mut rng) as u32).collect();
+        values.push(u32::MAX);
+
+        let k = 2;
+        let q_max = 24;
+        let mut coder = RiceCoder::with_escape(k, q_max);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&values, &mut encoded);
+
+        let mut decoded_values = Vec::new();
+        let num_bytes = coder.decode_into(&encoded, &mut decoded_values, values.len() as u32);
+        assert_eq!(num_bytes, encoded.len());
+        assert_eq!(decoded_values, values);
+
+        // Worst case per value is bounded by the escape
+        let max_bits = values.len() * (q_max as usize + 32) + 8;
+        assert!(encoded.len() * 8 <= max_bits);
+
+        let mut plain: Vec<u8> = Vec::new();
+        create_rice_coder(k).encode_vals(&values, &mut plain);
+        assert!(encoded.len() < plain.len());
+    }
+
+    proptest! {
+        #[test]
+        fn test_escape_random_values(values in prop::collection::vec(0u32..=500_000, 1..20), k in 1u8..8, q_max in 0u32..40) {
+            let mut coder = RiceCoder::with_escape(k, q_max);
+
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+
+            let mut decoded_values = Vec::new();
+            let num_bytes = coder.decode_into(&encoded, &mut decoded_values, values.len() as u32);
+            prop_assert_eq!(num_bytes, encoded.len());
+            prop_assert_eq!(values, decoded_values);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_decode_to_sink_matches_decode_into(values in prop::collection::vec(0u32..=5_000, 0..40), k in 0u8..8, num_values in 0u32..50) {
+            let mut coder = create_rice_coder(k);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+
+            let mut decoded_values = vec![7];
+            let num_bytes = coder.decode_into(&encoded, &mut decoded_values, num_values);
+            let mut sunk_values = vec![7];
+            let sink_num_bytes =
+                coder.decode_to_sink(&encoded, num_values, &mut |value| sunk_values.push(value));
+            prop_assert_eq!(sink_num_bytes, num_bytes);
+            prop_assert_eq!(&sunk_values, &decoded_values);
+            let expected_len = core::cmp::min(values.len(), num_values as usize);
+            prop_assert_eq!(&decoded_values[1..], &values[..expected_len]);
+        }
+    }
+
+    #[test]
+    fn test_decode_components() {
+        let mut coder = create_rice_coder(3);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&[37, 12, 5, 150, 0], &mut encoded);
+        let (quotients, remainders) = coder.decode_components(&encoded, 10);
+        assert_eq!(quotients, [4, 1, 0, 18, 0]);
+        assert_eq!(remainders, [5, 4, 5, 6, 0]);
+
+        // The escaped value is split like any other
+        let mut coder = RiceCoder::with_escape(2, 4);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&[3, 1000], &mut encoded);
+        let (quotients, remainders) = coder.decode_components(&encoded, 2);
+        assert_eq!(quotients, [0, 250]);
+        assert_eq!(remainders, [3, 0]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_decode_components_combine(values in prop::collection::vec(0u32..=5_000, 0..40), k in 0u8..12, num_values in 0u32..50) {
+            let mut coder = create_rice_coder(k);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+
+            let mut decoded_values = Vec::new();
+            coder.decode_into(&encoded, &mut decoded_values, num_values);
+            let (quotients, remainders) = coder.decode_components(&encoded, num_values);
+            prop_assert_eq!(quotients.len(), decoded_values.len());
+            let combined: Vec<u32> = quotients
+                .iter()
+                .zip(&remainders)
+                .map(|(quotient, remainder)| {
+                    assert!(*remainder < 1 << k);
+                    (quotient << k) + remainder
+                })
+                .collect();
+            prop_assert_eq!(combined, decoded_values);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_encoded_len_matches_encoding(values in prop::collection::vec(0u32..=500_000, 0..20), k in 0u8..16) {
+            let mut encoded: Vec<u8> = Vec::new();
+            create_rice_coder(k).encode_vals(&values, &mut encoded);
+            prop_assert_eq!(encoded_len_bytes(&values, k), encoded.len() as u64);
+        }
+    }
+
+    #[test]
+    fn test_encoded_len_bits() {
+        // 37 with k=3: 4 ones, a zero and 3 remainder bits
+        assert_eq!(encoded_len_bits(&[37], 3), 8);
+        assert_eq!(encoded_len_bytes(&[37], 3), 2);
+        assert_eq!(encoded_len_bits(&[], 3), 0);
+        assert_eq!(encoded_len_bytes(&[], 3), 1);
+        assert_eq!(encoded_len_bits(&[37, 12, 5, 150, 255, 0, 10], 3), 83);
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        // 37 with k=3 takes 2 bytes with the padding
+        assert_eq!(compression_ratio(&[37], 3), 2.0);
+        assert_eq!(compression_ratio(&[], 3), 0.0);
+
+        let (_, values) = bench_datasets()
+            .into_iter()
+            .find(|(name, _)| *name == "sorted values")
+            .unwrap();
+        let ratio = compression_ratio(&values, 7);
+        assert!(ratio > 1.0, "ratio = {}", ratio);
+        let mut encoded: Vec<u8> = Vec::new();
+        create_rice_coder(7).encode_vals(&values, &mut encoded);
+        assert_eq!(ratio, (values.len() * 4) as f64 / encoded.len() as f64);
+    }
+
+    #[test]
+    fn test_savings_vs_fixed() {
+        // 1000 takes 2 bytes at a fixed width and 11 bits as Rice code with k = 9 or 10
+        let report = savings_vs_fixed(&[1000; 8]);
+        assert_eq!(report.plain_bytes, 32);
+        assert_eq!(report.fixed_width_bytes, 16);
+        assert!((9..=10).contains(&report.best_k), "{:?}", report);
+        assert_eq!(report.rice_bytes, 12);
+        assert_eq!(report.percent_saved, 25.0);
+
+        let (_, values) = bench_datasets()
+            .into_iter()
+            .find(|(name, _)| *name == "sequential with gaps")
+            .unwrap();
+        let report = savings_vs_fixed(&values);
+        // Up to 2540, 12 bits
+        assert_eq!(report.fixed_width_bytes, 255 * 2);
+        assert_eq!(report.plain_bytes, 255 * 4);
+        assert_eq!(report.best_k, best_k_by_size(&values));
+        let mut encoded: Vec<u8> = Vec::new();
+        create_rice_coder(report.best_k).encode_vals(&values, &mut encoded);
+        assert_eq!(report.rice_bytes, encoded.len() as u64);
+        let expected = (1.0 - encoded.len() as f64 / 510.0) * 100.0;
+        assert_eq!(report.percent_saved, expected);
+        assert!(report.percent_saved > 0.0);
+
+        // Uniform random bytes gain nothing over a fixed width
+        assert!(savings_vs_fixed(&[0, 255, 17, 200, 99, 128]).percent_saved < 0.0);
+        let report = savings_vs_fixed(&[]);
+        assert_eq!((report.fixed_width_bytes, report.rice_bytes), (0, 1));
+        assert_eq!(report.percent_saved, 0.0);
+        // Zeros still take a byte each at a fixed width
+        assert_eq!(savings_vs_fixed(&[0; 4]).fixed_width_bytes, 4);
+    }
+
+    /// The sorted sample distributions used in `benches/bench.rs`
+    pub(crate) fn bench_datasets() -> Vec<(&'static str, Vec<u32>)> {
+        use rand::{prelude::Distribution, rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::from_seed([3u8; 32]);
+        let zipf = zipf::ZipfDistribution::new(2000, 1.5).unwrap();
+        let zipf2 = zipf::ZipfDistribution::new(200000, 1.5).unwrap();
+        let mut data: Vec<(&str, Vec<u32>)> = vec![
+            (
+                "sequential with gaps",
+                (0..255).map(|docid| docid * 10).collect(),
+            ),
+            ("sorted values", (0..255).collect()),
+            (
+                "random values u8::MAX",
+                (0..255).map(|_| rng.gen::<u8>() as u32).collect(),
+            ),
+            (
+                "random values u16::MAX",
+                (0..255).map(|_| rng.gen::<u16>() as u32).collect(),
+            ),
+            (
+                "zipfs values max 2000",
+                (0..255).map(|_| zipf.sample(&mut rng) as u32).collect(),
+            ),
+            (
+                "zipfs values max 200000",
+                (0..255).map(|_| zipf2.sample(&mut rng) as u32).collect(),
+            ),
+            (
+                "random values small range(0..5)",
+                (0..255).map(|_| rng.gen::<u8>() as u32 % 5).collect(),
+            ),
+        ];
+        for data in data.iter_mut() {
+            data.1.sort();
+        }
+        data
+    }
+
+    #[test]
+    fn test_best_k_by_size_beats_percentile_estimate() {
+        for (name, values) in bench_datasets() {
+            let best_k = best_k_by_size(&values);
+            let best_len = encoded_len_bits(&values, best_k);
+            for k in 0..=31 {
+                assert!(best_len <= encoded_len_bits(&values, k), "{}", name);
+            }
+            for percentile in [50.0, 80.0, 90.0, 100.0] {
+                let k = estimate_optimal_k(&values, percentile);
+                assert!(best_len <= encoded_len_bits(&values, k), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_analyze() {
+        let values: Vec<u32> = (0..100).map(|i| i % 10).collect();
+        let report = analyze(&values);
+        assert_eq!(report.k, best_k_by_size(&values));
+        let mut encoded = Vec::new();
+        create_rice_coder(report.k).encode_vals(&values, &mut encoded);
+        assert_eq!(report.rice_bytes, encoded.len() as u64);
+        assert_eq!(report.raw_bytes, 400);
+        assert_eq!(report.varint_bytes, 100);
+
+        let report = analyze(&[0, 127, 128, u32::MAX]);
+        assert_eq!(report.raw_bytes, 16);
+        assert_eq!(report.varint_bytes, 1 + 1 + 2 + 5);
+
+        let report = analyze(&[]);
+        assert_eq!(
+            (report.rice_bytes, report.raw_bytes, report.varint_bytes),
+            (1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_encode_vals_with_stats() {
+        let values = [0, 7, 8, 100, 1000];
+        let mut coder = create_rice_coder(3);
+        let mut encoded: Vec<u8> = Vec::new();
+        let stats = coder.encode_vals_with_stats(&values, &mut encoded);
+        assert_eq!(
+            stats,
+            EncodeStats {
+                num_values: 5,
+                total_bits: encoded_len_bits(&values, 3),
+                max_unary_run: 125,
+                long_quotients: 1,
+            }
+        );
+        assert_eq!(stats.bits_per_value(), stats.total_bits as f64 / 5.0);
+
+        let mut expected: Vec<u8> = Vec::new();
+        create_rice_coder(3).encode_vals(&values, &mut expected);
+        assert_eq!(encoded, expected);
+
+        // Escaped values cost `q_max + 32` bits
+        let mut coder = RiceCoder::with_escape(3, 10);
+        let stats = coder.encode_vals_with_stats(&values, &mut encoded);
+        assert_eq!(stats.max_unary_run, 10);
+        assert_eq!(stats.long_quotients, 0);
+        assert_eq!(stats.total_bits, 4 + 4 + 5 + 2 * (10 + 32));
+
+        let stats = coder.encode_vals_with_stats(&[], &mut encoded);
+        assert_eq!(stats, EncodeStats::default());
+        assert_eq!(stats.bits_per_value(), 0.0);
+    }
+
+    #[test]
+    fn test_estimate_k_streaming() {
+        assert_eq!(estimate_k_streaming(core::iter::empty()), 0);
+        assert_eq!(estimate_k_streaming([0, 0, 1].into_iter()), 0);
+        assert_eq!(estimate_k_streaming(core::iter::repeat_n(100, 10)), 6);
+        assert_eq!(estimate_k_streaming(core::iter::repeat_n(u32::MAX, 8)), 31);
+
+        for (name, values) in bench_datasets() {
+            let from_iter = estimate_k_streaming(values.iter().copied());
+            assert_eq!(from_iter, estimate_k_from_mean(&values), "{}", name);
+        }
+        let values: Vec<u32> = (0..1000).map(|i| i * 7 % 113).collect();
+        // mean 56.3 -> log2(39.0) = 5.3
+        assert_eq!(estimate_k_streaming(values.iter().copied()), 5);
+        assert_eq!(estimate_k_from_mean(&values), 5);
+    }
+
+    #[test]
+    fn test_estimate_k_from_mean() {
+        assert_eq!(estimate_k_from_mean(&[]), 0);
+        assert_eq!(estimate_k_from_mean(&[0, 0, 1]), 0);
+        // mean 100 -> log2(69.3) = 6.1
+        assert_eq!(estimate_k_from_mean(&[100; 10]), 6);
+        assert_eq!(estimate_k_from_mean(&[u32::MAX; 4]), 31);
+
+        for (name, values) in bench_datasets() {
+            let k = estimate_k_from_mean(&values);
+            let best_k = best_k_by_size(&values);
+            assert!(k.abs_diff(best_k) <= 1, "{}: {} vs {}", name, k, best_k);
+        }
+    }
+
+    #[test]
+    fn test_clone_mid_stream() {
+        let mut coder = create_rice_coder(3);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode(37, &mut encoded);
+        coder.encode(5, &mut encoded);
+
+        let mut forked = coder;
+        assert_eq!(forked, coder);
+        let mut forked_encoded = encoded.clone();
+
+        let rest = [150, 255, 0, 10];
+        for value in rest {
+            coder.encode(value, &mut encoded);
+            forked.encode(value, &mut forked_encoded);
+        }
+        coder.finalize(&mut encoded);
+        forked.finalize(&mut forked_encoded);
+        assert_eq!(encoded, forked_encoded);
+        assert_eq!(forked, coder);
+    }
+
+    /// Bit-by-bit reference of the stream format: unary quotient, `0`, `k` remainder bits and
+    /// `1`s padding to the next byte
+    fn reference_encode(values: &[u32], k: u8) -> Vec<u8> {
+        let mut bits: Vec<bool> = Vec::new();
+        for value in values {
+            bits.extend(std::iter::repeat_n(true, (value >> k) as usize));
+            bits.push(false);
+            bits.extend((0..k).rev().map(|bit| (value >> bit) & 1 == 1));
+        }
+        bits.extend(std::iter::repeat_n(true, 8 - bits.len() % 8));
+        bits.chunks(8)
+            .map(|byte| byte.iter().fold(0u8, |acc, bit| (acc << 1) | *bit as u8))
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn test_encoding_matches_reference(values in prop::collection::vec(0u32..=500_000, 0..40), k in 0u8..32) {
+            let mut encoded: Vec<u8> = Vec::new();
+            create_rice_coder(k).encode_vals(&values, &mut encoded);
+            prop_assert_eq!(encoded, reference_encode(&values, k));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_unary_matches_general_path(values in prop::collection::vec(prop_oneof![0u32..8, 0u32..200], 0..80), num_values in 0u32..100, order in prop_oneof![Just(BitOrder::MsbFirst), Just(BitOrder::LsbFirst)]) {
+            let mut coder = RiceCoder::with_bit_order(0, order);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+            if order == BitOrder::MsbFirst {
+                prop_assert_eq!(&encoded, &reference_encode(&values, 0));
+            }
+
+            let mut decoded_values = Vec::new();
+            let num_bytes = coder.decode_into(&encoded, &mut decoded_values, num_values);
+            let expected_len = core::cmp::min(values.len(), num_values as usize);
+            prop_assert_eq!(&decoded_values[..], &values[..expected_len]);
+            // Value by value, without the word at a time path
+            let mut reader = coder.reader(&encoded);
+            for _ in 0..expected_len {
+                coder.decode_value(&mut reader);
+            }
+            if expected_len < num_values as usize {
+                prop_assert_eq!(coder.decode_value(&mut reader), None);
+            }
+            prop_assert_eq!(num_bytes, core::cmp::min(reader.byte_pos() + 1, encoded.len()));
+        }
+    }
+
+    #[test]
+    fn test_adaptive_roundtrip() {
+        // Small values followed by large ones, so a single k is suboptimal
+        let values: Vec<u32> = (0..300)
+            .map(|i| if i < 150 { i % 4 } else { 100_000 + i })
+            .collect();
+        let mut coder = create_rice_coder(0);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_adaptive(&values, 64, &mut encoded);
+
+        let mut decoded_values = Vec::new();
+        let num_bytes = coder
+            .decode_adaptive(&encoded, &mut decoded_values)
+            .unwrap();
+        assert_eq!(num_bytes, encoded.len());
+        assert_eq!(decoded_values, values);
+
+        let single_k = encoded_len_bytes(&values, best_k_by_size(&values));
+        assert!((encoded.len() as u64) < single_k);
+    }
+
+    #[test]
+    fn test_adaptive_errors() {
+        let coder = create_rice_coder(0);
+        let mut out = Vec::new();
+        assert_eq!(
+            coder.decode_adaptive(&[], &mut out),
+            Err(DecodeError::MissingHeader)
+        );
+        assert_eq!(
+            coder.decode_adaptive(&[4, 1, 40, 0xFF], &mut out),
+            Err(DecodeError::InvalidK(40))
+        );
+        assert_eq!(
+            coder.decode_adaptive(&[0, 1, 3, 0xFF], &mut out),
+            Err(DecodeError::InvalidBlockSize)
+        );
+        // Second block of 4 values, but the stream ends after the first one
+        assert_eq!(
+            coder.decode_adaptive(&[4, 8, 0, 0b0000_1111, 0, 0b0111_1111], &mut out),
+            Err(DecodeError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn test_adaptive_partial_last_block() {
+        // Blocks of 4, 4 and 2 values
+        let values = [0, 1, 0, 2, 40, 50, 60, 70, 1000, 0];
+        let mut coder = create_rice_coder(0);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_adaptive(&values, 4, &mut encoded);
+        assert_eq!(&encoded[..2], &[4, 10]);
+        assert_eq!(coder.k(), best_k_by_size(&values[8..]));
+
+        let mut decoded_values = vec![7];
+        let num_bytes = coder
+            .decode_adaptive(&encoded, &mut decoded_values)
+            .unwrap();
+        assert_eq!(num_bytes, encoded.len());
+        assert_eq!(decoded_values[1..], values);
+
+        // A block size larger than the input is a single partial block
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_adaptive(&values, 64, &mut encoded);
+        let mut decoded_values = Vec::new();
+        coder
+            .decode_adaptive(&encoded, &mut decoded_values)
+            .unwrap();
+        assert_eq!(decoded_values, values);
+    }
+
+    #[test]
+    fn test_deltas_roundtrip() {
+        for (name, values) in bench_datasets() {
+            let mut coder = create_rice_coder(2);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_deltas(&values, &mut encoded);
+
+            let mut decoded_values = vec![7];
+            let num_bytes =
+                coder.decode_deltas_into(&encoded, &mut decoded_values, values.len() as u32);
+            assert_eq!(num_bytes, encoded.len(), "{}", name);
+            assert_eq!(decoded_values[1..], values, "{}", name);
+        }
+
+        let sorted: Vec<u32> = (0..255).collect();
+        let mut delta_encoded: Vec<u8> = Vec::new();
+        create_rice_coder(1).encode_deltas(&sorted, &mut delta_encoded);
+        let plain_len = encoded_len_bytes(&sorted, best_k_by_size(&sorted));
+        assert!((delta_encoded.len() as u64) < plain_len / 2);
+    }
+
+    #[test]
+    fn test_contains_sorted() {
+        for (name, values) in bench_datasets()
+            .into_iter()
+            .filter(|(name, _)| ["sorted values", "sequential with gaps"].contains(name))
+        {
+            let coder = create_rice_coder(2);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.clone().encode_deltas(&values, &mut encoded);
+            let num_values = values.len() as u32;
+            for target in 0..=values[values.len() - 1] + 20 {
+                assert_eq!(
+                    coder.contains_sorted(&encoded, num_values, target),
+                    values.binary_search(&target).is_ok(),
+                    "{}: {}",
+                    name,
+                    target
+                );
+            }
+            // Only the first `num_values` values are searched
+            assert!(coder.contains_sorted(&encoded, 5, values[4]));
+            assert!(!coder.contains_sorted(&encoded, 5, values[5]));
+        }
+
+        assert!(!create_rice_coder(2).contains_sorted(&[], 10, 0));
+    }
+
+    #[test]
+    fn test_deltas_extremes() {
+        let values = [u32::MAX, u32::MAX];
+        let mut coder = create_rice_coder(31);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_deltas(&values, &mut encoded);
+        let mut decoded_values = Vec::new();
+        coder.decode_deltas_into(&encoded, &mut decoded_values, 2);
+        assert_eq!(decoded_values, values);
+    }
+
+    proptest! {
+        #[test]
+        fn test_encode_iter_matches_encode_vals(values in prop::collection::vec(0u32..=500_000, 0..40), k in 0u8..16) {
+            let mut encoded: Vec<u8> = Vec::new();
+            create_rice_coder(k).encode_vals(&values, &mut encoded);
+            let mut encoded_iter: Vec<u8> = Vec::new();
+            create_rice_coder(k).encode_iter(values.iter().copied(), &mut encoded_iter);
+            prop_assert_eq!(encoded, encoded_iter);
+        }
+    }
+
+    #[test]
+    fn test_encode_iter_chained() {
+        let mut encoded: Vec<u8> = Vec::new();
+        create_rice_coder(2).encode_iter(
+            (0..100u32).filter(|v| v % 3 == 0).map(|v| v * 2),
+            &mut encoded,
+        );
+        let mut decoded_values = Vec::new();
+        create_rice_coder(2).decode_all_into(&encoded, &mut decoded_values);
+        assert_eq!(
+            decoded_values,
+            (0..100u32).step_by(3).map(|v| v * 2).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_try_decode_truncated() {
+        let values: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        let mut coder = create_rice_coder(3);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&values, &mut encoded);
+
+        let mut decoded_values = Vec::new();
+        let num_bytes = coder.try_decode_into(&encoded, &mut decoded_values, 7);
+        assert_eq!(num_bytes, Ok(encoded.len()));
+        assert_eq!(decoded_values, values);
+
+        // Padding only, asking for too many values is fine
+        let mut decoded_values = Vec::new();
+        assert!(coder
+            .try_decode_into(&encoded, &mut decoded_values, 8)
+            .is_ok());
+        assert_eq!(decoded_values, values);
+
+        // Cut inside the unary run of 150
+        let mut decoded_values = Vec::new();
+        let result = coder.try_decode_into(&encoded[..4], &mut decoded_values, 7);
+        assert_eq!(result, Err(DecodeError::UnexpectedEof));
+        assert_eq!(decoded_values, [37, 12, 5]);
+
+        // Cut inside a remainder: 8 and 5 are 10000 0101, the remainder of 5 crosses the byte
+        let mut decoded_values = Vec::new();
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&[8, 5], &mut encoded);
+        let result = coder.try_decode_into(&encoded[..1], &mut decoded_values, 2);
+        assert_eq!(result, Err(DecodeError::UnexpectedEof));
+    }
+
+    proptest! {
+        #[test]
+        fn test_decode_arbitrary_input_never_panics(
+            input in prop::collection::vec(prop_oneof![Just(0xFFu8), Just(0u8), any::<u8>()], 0..64),
+            k in 0u8..=31,
+            escape in prop::option::of(0u32..64),
+            num_values in prop_oneof![Just(u32::MAX), 0u32..100],
+        ) {
+            let coder = RiceCoder { escape, ..RiceCoder::new(k) };
+            let mut out = Vec::new();
+            prop_assert!(coder.decode_into(&input, &mut out, num_values) <= input.len());
+            // Every value takes at least one bit
+            prop_assert!(out.len() <= input.len() * 8);
+            coder.decode_all_into(&input, &mut out);
+            let _ = coder.try_decode_into(&input, &mut out, num_values);
+            let _ = coder.decode_counted_into(&input, &mut out);
+            let _ = coder.decode_adaptive(&input, &mut out);
+            coder.decode_deltas_into(&input, &mut out, num_values);
+            coder.decode_signed_into(&input, &mut Vec::new(), num_values);
+            let result = coder.decode_into_result(&input, &mut out, num_values);
+            prop_assert!(result.bytes_consumed <= input.len());
+            coder.decode_into_slice(&input, &mut [0; 16]);
+            coder.decode_bits_into(&input, input.len() as u64 * 8, &mut out);
+            coder.decode_chunks(&input, 7, |_| {});
+            let _ = decode_with_header(&input);
+            let _ = RiceStreamDecoder::new(&input).decode_all();
+        }
+    }
+
+    #[test]
+    fn test_decode_framed() {
+        let first: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        // Ends on a byte boundary, so the padding is a full byte
+        let second: Vec<u32> = vec![0; 8];
+        let mut output: Vec<u8> = Vec::new();
+        create_rice_coder(3).encode_vals(&first, &mut output);
+        create_rice_coder(0).encode_vals(&second, &mut output);
+        output.extend_from_slice(b"next");
+
+        let (values, tail) = create_rice_coder(3).decode_framed(&output, first.len() as u32);
+        assert_eq!(values, first);
+        let (values, tail) = create_rice_coder(0).decode_framed(tail, second.len() as u32);
+        assert_eq!(values, second);
+        assert_eq!(tail, b"next");
+
+        let (values, tail) = create_rice_coder(3).decode_framed(&[], 3);
+        assert!(values.is_empty() && tail.is_empty());
+    }
+
+    #[test]
+    fn test_decode_exact_count_mismatch() {
+        let values: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        let mut coder = create_rice_coder(3);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&values, &mut encoded);
+
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            coder.decode_exact_into(&encoded, &mut decoded_values, 7),
+            Ok(encoded.len())
+        );
+        assert_eq!(decoded_values, values);
+
+        // Off by one
+        assert_eq!(
+            coder.decode_exact_into(&encoded, &mut decoded_values, 8),
+            Err(DecodeError::CountMismatch {
+                expected: 8,
+                got: 7
+            })
+        );
+        assert_eq!(
+            coder.decode_exact_into(&[], &mut decoded_values, 1),
+            Err(DecodeError::CountMismatch {
+                expected: 1,
+                got: 0
+            })
+        );
+        // Fewer values than encoded are fine
+        assert!(coder
+            .decode_exact_into(&encoded, &mut decoded_values, 3)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_try_decode_verified() {
+        let values = [3, 0, 9, 1];
+        let mut coder = create_rice_coder(2);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&values, &mut encoded);
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            coder.try_decode_verified_into(&encoded, &mut decoded_values, 4),
+            Ok(encoded.len())
+        );
+        assert_eq!(decoded_values, values);
+        // Requesting more values than encoded stops at the padding
+        decoded_values.clear();
+        assert_eq!(
+            coder.try_decode_verified_into(&encoded, &mut decoded_values, 10),
+            Ok(encoded.len())
+        );
+
+        let mut corrupt = encoded.clone();
+        *corrupt.last_mut().unwrap() ^= 0xFF;
+        decoded_values.clear();
+        assert_eq!(
+            coder.try_decode_verified_into(&corrupt, &mut decoded_values, 4),
+            Err(DecodeError::BadPadding)
+        );
+
+        // A stream ending on a byte boundary needs its full padding byte
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&[0, 1, 2, 3, 4, 5], &mut encoded);
+        assert_eq!(encoded.len(), 3);
+        decoded_values.clear();
+        assert_eq!(
+            coder.try_decode_verified_into(&encoded[..2], &mut decoded_values, 6),
+            Err(DecodeError::BadPadding)
+        );
+    }
+
+    #[test]
+    fn test_run_length_limited_unary() {
+        // max = 2: 0 -> 0 0, 2 -> 10 0, 5 -> 11 1, 4 -> 11 0
+        let style = UnaryStyle::RunLengthLimited { max: 2 };
+        let mut coder = RiceCoder::with_unary_style(1, style);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&[0, 2, 5, 4], &mut encoded);
+        assert_eq!(encoded, [0b0010_0111, 0b1101_1111]);
+
+        let mut decoded_values = Vec::new();
+        assert_eq!(coder.decode_into(&encoded, &mut decoded_values, 4), 2);
+        assert_eq!(decoded_values, [0, 2, 5, 4]);
+
+        // max = 0 is plain binary
+        let mut coder = RiceCoder::with_unary_style(4, UnaryStyle::RunLengthLimited { max: 0 });
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&[0xA, 0x5], &mut encoded);
+        assert_eq!(encoded, [0xA5, 0xFF]);
+    }
+
+    #[test]
+    #[should_panic(expected = "quotient 3 exceeds the unary run length limit 2")]
+    fn test_run_length_limited_unary_too_large() {
+        let mut coder = RiceCoder::with_unary_style(1, UnaryStyle::RunLengthLimited { max: 2 });
+        coder.encode(6, &mut Vec::new());
+    }
+
+    #[test]
+    fn test_run_length_limited_unary_checked() {
+        let mut coder = RiceCoder::with_unary_style(1, UnaryStyle::RunLengthLimited { max: 2 });
+        let mut encoded: Vec<u8> = Vec::new();
+        assert_eq!(coder.checked_encode(5, &mut encoded), Ok(()));
+        assert_eq!(
+            coder.checked_encode(6, &mut encoded),
+            Err(EncodeError::RunTooLong { value: 6, max: 2 })
+        );
+        coder.finalize(&mut encoded);
+        let mut decoded_values = Vec::new();
+        coder.decode_into(&encoded, &mut decoded_values, 1);
+        assert_eq!(decoded_values, [5]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_unary_style_roundtrip(values in prop::collection::vec(0u32..=64, 0..60), k in 0u8..4, max in prop::option::of(0u32..8)) {
+            let style = match max {
+                Some(max) => UnaryStyle::RunLengthLimited { max },
+                None => UnaryStyle::ZeroTerminated,
+            };
+            let mut coder = RiceCoder::with_unary_style(k, style);
+            let coder_max = coder.max_safe_value();
+            let values: Vec<u32> = values.iter().map(|value| core::cmp::min(*value, coder_max)).collect();
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+
+            let mut decoded_values = Vec::new();
+            coder.decode_into(&encoded, &mut decoded_values, values.len() as u32);
+            prop_assert_eq!(&decoded_values, &values);
+            let mut decoded_values = Vec::new();
+            prop_assert!(coder.try_decode_into(&encoded, &mut decoded_values, values.len() as u32).is_ok());
+            prop_assert_eq!(&decoded_values, &values);
+            let mut decoded_values: Vec<u64> = Vec::new();
+            coder.decode_into_as(&encoded, &mut decoded_values, values.len() as u32);
+            prop_assert!(decoded_values.iter().map(|value| *value as u32).eq(values.iter().copied()));
+        }
+    }
+
+    #[test]
+    fn test_decode_empty_and_padding_only() {
+        // Table driven and bitwise decoding
+        for k in [0, 2, 3, 17] {
+            let coder = create_rice_coder(k);
+            let mut decoded_values = Vec::new();
+            assert_eq!(coder.decode_into(&[], &mut decoded_values, 10), 0);
+            assert_eq!(coder.decode_all_into(&[], &mut decoded_values), 0);
+            assert_eq!(coder.try_decode_into(&[], &mut decoded_values, 10), Ok(0));
+            assert_eq!(
+                coder
+                    .decode_into_result(&[], &mut decoded_values, 10)
+                    .bytes_consumed,
+                0
+            );
+            assert_eq!(coder.iter_decode(&[], 10).count(), 0);
+
+            for padding in [&[0xFF][..], &[0xFF, 0xFF, 0xFF]] {
+                let num_bytes = coder.decode_into(padding, &mut decoded_values, 10);
+                assert_eq!(num_bytes, padding.len());
+                assert_eq!(
+                    coder.decode_all_into(padding, &mut decoded_values),
+                    padding.len()
+                );
+                assert_eq!(coder.iter_decode(padding, 10).count(), 0);
+            }
+            // `finalize` writes at most one padding byte, a longer run is a truncated value
+            assert_eq!(
+                coder.try_decode_into(&[0xFF], &mut decoded_values, 10),
+                Ok(1)
+            );
+            assert_eq!(
+                coder.try_decode_into(&[0xFF, 0xFF], &mut decoded_values, 10),
+                Err(DecodeError::UnexpectedEof)
+            );
+            assert!(decoded_values.is_empty());
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_encode_vals_no_finalize_concatenation(a in prop::collection::vec(0u32..=10_000, 0..40), b in prop::collection::vec(0u32..=10_000, 0..40), k in 0u8..12) {
+            let mut coder = create_rice_coder(k);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals_no_finalize(&a, &mut encoded);
+            coder.encode_vals_no_finalize(&b, &mut encoded);
+            coder.finalize(&mut encoded);
+
+            let mut expected: Vec<u8> = Vec::new();
+            coder.encode_vals(&[a, b].concat(), &mut expected);
+            prop_assert_eq!(encoded, expected);
+        }
+    }
+
+    #[test]
+    fn test_matrix_roundtrip() {
+        let rows: [&[u32]; 4] = [
+            &[0, 1, 2, 3],
+            &[100, 0, 7, 1],
+            &[5000, 2, 2, 0],
+            &[1, 1, 65_535, 9],
+        ];
+        let mut coder = create_rice_coder(0);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_matrix(&rows, 4, &mut encoded);
+        assert_eq!(&encoded[..3], [4, 4, 4]);
+        assert_eq!(coder.k(), 4);
+
+        let mut matrix = Vec::new();
+        assert_eq!(
+            coder.decode_matrix_into(&encoded, 4, 4, &mut matrix),
+            Ok(encoded.len())
+        );
+        assert_eq!(matrix, rows);
+
+        assert_eq!(
+            coder.decode_matrix_into(&encoded, 2, 8, &mut matrix),
+            Err(DecodeError::DimensionMismatch {
+                num_rows: 4,
+                row_len: 4
+            })
+        );
+        assert_eq!(
+            coder.decode_matrix_into(&encoded[..6], 4, 4, &mut matrix),
+            Err(DecodeError::UnexpectedEof)
+        );
+        assert_eq!(matrix.len(), 4);
+
+        // Rows without values
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_matrix(&[&[], &[]], 0, &mut encoded);
+        let mut matrix = Vec::new();
+        coder
+            .decode_matrix_into(&encoded, 0, 2, &mut matrix)
+            .unwrap();
+        assert_eq!(matrix, [Vec::<u32>::new(), Vec::new()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "all rows must have the same length")]
+    fn test_matrix_ragged_rows() {
+        create_rice_coder(0).encode_matrix(&[&[1, 2], &[3]], 1, &mut Vec::new());
+    }
+
+    proptest! {
+        #[test]
+        fn test_decode_segments_matches_separate_decodes(segments in prop::collection::vec((prop::collection::vec(0u32..=10_000, 0..30), 0u8..12), 0..6)) {
+            let encoded: Vec<(Vec<u8>, u8, u32)> = segments
+                .iter()
+                .map(|(values, k)| {
+                    let mut encoded = Vec::new();
+                    create_rice_coder(*k).encode_vals(values, &mut encoded);
+                    (encoded, *k, values.len() as u32)
+                })
+                .collect();
+            let segment_refs: Vec<(&[u8], u8, u32)> = encoded
+                .iter()
+                .map(|(input, k, num_vals)| (&input[..], *k, *num_vals))
+                .collect();
+
+            let mut expected = Vec::new();
+            for (input, k, num_vals) in &segment_refs {
+                create_rice_coder(*k).decode_into(input, &mut expected, *num_vals);
+            }
+            let mut decoded_values = vec![7];
+            prop_assert_eq!(decode_segments_into(&segment_refs, &mut decoded_values), expected.len());
+            prop_assert_eq!(&decoded_values[1..], &expected[..]);
+            let all_values: Vec<u32> = segments.iter().flat_map(|(values, _)| values.iter().copied()).collect();
+            prop_assert_eq!(expected, all_values);
+        }
+    }
+
+    #[test]
+    fn test_encode_to_boxed() {
+        let values: Vec<u32> = (0..300).map(|i| i * 17 % 90).collect();
+        let boxed = encode_to_boxed(&values, 5);
+        assert_eq!(boxed.len(), encoded_len_bytes(&values, 5) as usize);
+
+        let mut decoded_values = Vec::new();
+        create_rice_coder(5).decode_into(&boxed, &mut decoded_values, 300);
+        assert_eq!(decoded_values, values);
+        assert_eq!(&*encode_to_boxed(&[], 3), [0xFF]);
+    }
+
+    #[test]
+    fn test_reserve_for_output() {
+        let values: Vec<u32> = (0..1000).map(|i| i * 31 % 100).collect();
+        assert_eq!(
+            reserve_for(&values, 4),
+            encoded_len_bytes(&values, 4) as usize
+        );
+        let mut coder = create_rice_coder(4);
+        let mut encoded: Vec<u8> = Vec::with_capacity(reserve_for(&values, 4));
+        coder.encode_vals(&values, &mut encoded);
+        assert_eq!(encoded.len(), reserve_for(&values, 4));
+    }
+
+    #[test]
+    fn test_encode_vals_escape_huge_values() {
+        // The plain Rice codes of these would take about 500 GB
+        let values = vec![u32::MAX; 1000];
+        let mut coder = RiceCoder::with_escape(0, 16);
+        assert_eq!(coder.reserve_len(&values), 1000 * 48 / 8 + 1);
+        assert_eq!(
+            RiceCoder::new(4).reserve_len(&values[..2]),
+            reserve_for(&values[..2], 4)
+        );
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&values, &mut encoded);
+        assert!(encoded.len() < 1000 * 8);
+        let mut decoded_values = Vec::new();
+        coder.decode_into(&encoded, &mut decoded_values, 1000);
+        assert_eq!(decoded_values, values);
+    }
+
+    #[test]
+    fn test_encode_with_offset() {
+        let values: Vec<u32> = (0..200).map(|i| 3_000_000_000 + i * 13 % 64).collect();
+        let mut coder = create_rice_coder(5);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_with_offset(&values, 3_000_000_000, &mut encoded);
+        // Offset varint, plus at most 2 + 5 bits per value
+        assert!(encoded.len() <= 5 + 200 * 7 / 8 + 1);
+
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            coder.decode_with_offset_into(&encoded, &mut decoded_values, 200),
+            Ok(encoded.len())
+        );
+        assert_eq!(decoded_values, values);
+
+        let mut encoded: Vec<u8> = Vec::new();
+        assert_eq!(
+            coder.checked_encode_with_offset(&[10, 100], 10, &mut encoded),
+            Ok(())
+        );
+        decoded_values.clear();
+        coder
+            .decode_with_offset_into(&encoded, &mut decoded_values, 2)
+            .unwrap();
+        assert_eq!(decoded_values, [10, 100]);
+        let mut rejected: Vec<u8> = Vec::new();
+        assert_eq!(
+            coder.checked_encode_with_offset(&[10, 5, 100], 10, &mut rejected),
+            Err(EncodeError::BelowOffset {
+                value: 5,
+                offset: 10
+            })
+        );
+        assert!(rejected.is_empty());
+
+        assert_eq!(
+            coder.decode_with_offset_into(&[], &mut decoded_values, 1),
+            Err(DecodeError::MissingHeader)
+        );
+        // Offset u32::MAX followed by the values 0 and 1
+        let mut encoded: Vec<u8> = Vec::new();
+        write_varint(u32::MAX as u64, &mut encoded);
+        create_rice_coder(5).encode_vals(&[0, 1], &mut encoded);
+        assert_eq!(
+            coder.decode_with_offset_into(&encoded, &mut decoded_values, 2),
+            Err(DecodeError::Overflow)
+        );
+        assert_eq!(decoded_values, [10, 100]);
+
+        let mut encoded: Vec<u8> = Vec::new();
+        write_varint(u32::MAX as u64 + 1, &mut encoded);
+        create_rice_coder(5).encode_vals(&[0], &mut encoded);
+        assert_eq!(
+            coder.decode_with_offset_into(&encoded, &mut decoded_values, 1),
+            Err(DecodeError::Overflow)
+        );
+        assert_eq!(decoded_values, [10, 100]);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "value 5 is below the offset 10")]
+    fn test_encode_with_offset_below_offset_panics_in_debug() {
+        create_rice_coder(5).encode_with_offset(&[5, 100], 10, &mut Vec::new());
+    }
+
+    proptest! {
+        #[test]
+        fn test_transcode_matches_decode_encode(values in prop::collection::vec(0u32..=10_000, 0..60), k in 0u8..12, new_k in 0u8..16, lsb_first: bool) {
+            let order = if lsb_first { BitOrder::LsbFirst } else { BitOrder::MsbFirst };
+            let mut coder = RiceCoder::with_bit_order(k, order);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+
+            let mut decoded_values = Vec::new();
+            coder.decode_into(&encoded, &mut decoded_values, values.len() as u32);
+            let mut expected: Vec<u8> = Vec::new();
+            RiceCoder::with_bit_order(new_k, order).encode_vals(&decoded_values, &mut expected);
+
+            prop_assert_eq!(coder.transcode(&encoded, values.len() as u32, new_k), expected);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_decode_into_as_matches_u32(values in prop::collection::vec(0u32..=100_000, 0..60), k in 0u8..16, escape: bool) {
+            let mut coder = if escape { RiceCoder::with_escape(k, 4) } else { create_rice_coder(k) };
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+
+            let mut decoded_u32 = Vec::new();
+            let num_bytes = coder.decode_into(&encoded, &mut decoded_u32, values.len() as u32);
+            let mut decoded_u64: Vec<u64> = Vec::new();
+            prop_assert_eq!(coder.decode_into_as(&encoded, &mut decoded_u64, values.len() as u32), num_bytes);
+            let mut decoded_usize: Vec<usize> = Vec::new();
+            coder.decode_into_as(&encoded, &mut decoded_usize, values.len() as u32);
+            let mut decoded_as_u32: Vec<u32> = Vec::new();
+            coder.decode_into_as(&encoded, &mut decoded_as_u32, values.len() as u32);
+
+            prop_assert_eq!(&decoded_as_u32, &decoded_u32);
+            prop_assert!(decoded_u64.iter().map(|value| *value as u32).eq(decoded_u32.iter().copied()));
+            prop_assert!(decoded_usize.iter().map(|value| *value as u32).eq(decoded_u32.iter().copied()));
+        }
+    }
+
+    #[test]
+    fn test_decode_into_as_wide_values() {
+        // Values beyond u32 written by the 64-bit coder
+        let values = [0, u32::MAX as u64 + 1, 1 << 40];
+        let mut encoded: Vec<u8> = Vec::new();
+        RiceCoder64::new(31).encode_vals(&values, &mut encoded);
+
+        let coder = create_rice_coder(31);
+        let mut decoded_values: Vec<u64> = Vec::new();
+        assert_eq!(
+            coder.decode_into_as(&encoded, &mut decoded_values, 3),
+            encoded.len()
+        );
+        assert_eq!(decoded_values, values);
+        // They don't fit into u32
+        let mut decoded_values: Vec<u32> = Vec::new();
+        coder.decode_into_as(&encoded, &mut decoded_values, 3);
+        assert_eq!(decoded_values, [0]);
+    }
+
+    #[test]
+    fn test_decode_range() {
+        let values: Vec<u32> = (0..100).map(|i| i * 7 % 23).collect();
+        let mut coder = create_rice_coder(3);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&values, &mut encoded);
+
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            coder.decode_range_into(&encoded, 90, 20, &mut decoded_values),
+            encoded.len()
+        );
+        assert_eq!(decoded_values, values[90..]);
+        decoded_values.clear();
+        coder.decode_range_into(&encoded, 200, 20, &mut decoded_values);
+        assert!(decoded_values.is_empty());
+    }
+
+    proptest! {
+        #[test]
+        fn test_decode_range_matches_decode_into(values in prop::collection::vec(0u32..=10_000, 0..60), skip in 0u32..70, take in 0u32..70, k in 0u8..12) {
+            let mut coder = create_rice_coder(k);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+
+            let mut all = Vec::new();
+            coder.decode_into(&encoded, &mut all, values.len() as u32);
+            let start = core::cmp::min(skip as usize, all.len());
+            let end = core::cmp::min(start + take as usize, all.len());
+
+            let mut decoded_values = Vec::new();
+            coder.decode_range_into(&encoded, skip, take, &mut decoded_values);
+            prop_assert_eq!(&decoded_values[..], &all[start..end]);
+        }
+    }
+
+    #[test]
+    fn test_max_safe_value() {
+        for k in 0..=MAX_K {
+            assert_eq!(create_rice_coder(k).max_safe_value(), u32::MAX);
+            assert_eq!(RiceCoder::with_escape(k, 4).max_safe_value(), u32::MAX);
+        }
+        let style = UnaryStyle::RunLengthLimited { max: 2 };
+        assert_eq!(RiceCoder::with_unary_style(1, style).max_safe_value(), 5);
+        assert_eq!(RiceCoder::with_max_quotient(3, 10).max_safe_value(), 87);
+        assert_eq!(
+            RiceCoder::with_max_quotient(31, 10).max_safe_value(),
+            u32::MAX
+        );
+        let coder = RiceCoderBuilder::new()
+            .k(2)
+            .unary_style(style)
+            .max_quotient(1)
+            .build();
+        assert_eq!(coder.max_safe_value(), 7);
+        // The largest quotient still decodes, small `k` would take gigabytes
+        for k in [16, 24, 30, 31] {
+            let coder = create_rice_coder(k);
+            let mut encoded: Vec<u8> = Vec::new();
+            create_rice_coder(k).encode_vals(&[coder.max_safe_value()], &mut encoded);
+            let mut decoded_values = Vec::new();
+            assert_eq!(
+                coder.try_decode_into(&encoded, &mut decoded_values, 2),
+                Ok(encoded.len())
+            );
+            assert_eq!(decoded_values, [u32::MAX]);
+        }
+    }
+
+    #[test]
+    fn test_try_decode_stops_at_max_quotient() {
+        // k = 30, the largest quotient is 3, so the fourth `1` already overflows
+        let coder = create_rice_coder(30);
+        let mut decoded_values = Vec::new();
+        let mut encoded = vec![0xFF; 1000];
+        encoded.push(0);
+        assert_eq!(
+            coder.try_decode_into(&encoded, &mut decoded_values, 1),
+            Err(DecodeError::Overflow)
+        );
+        // A run of `1`s up to the end of the input is the padding
+        assert_eq!(
+            coder.try_decode_into(&[0xFF], &mut decoded_values, 1),
+            Ok(1)
+        );
+        assert!(decoded_values.is_empty());
+    }
+
+    #[test]
+    fn test_try_decode_run_too_long() {
+        // A megabyte of `1`s, which k = 0 would read up to the largest quotient of a `u32`
+        let mut encoded = vec![0xFF; 1 << 20];
+        encoded.push(0);
+        let coder = RiceCoder::with_max_quotient(0, 1000);
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            coder.try_decode_into(&encoded, &mut decoded_values, 1),
+            Err(DecodeError::RunTooLong)
+        );
+        // Stops instead of decoding with errors ignored
+        assert_eq!(coder.decode_into(&encoded, &mut decoded_values, 1), 126);
+        assert!(decoded_values.is_empty());
+
+        // Quotients up to the bound and the padding decode
+        let mut coder = RiceCoder::with_max_quotient(3, 5);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&[47, 0, 40], &mut encoded);
+        assert_eq!(
+            coder.try_decode_into(&encoded, &mut decoded_values, 4),
+            Ok(encoded.len())
+        );
+        assert_eq!(decoded_values, [47, 0, 40]);
+        encoded.clear();
+        coder.encode_vals(&[48], &mut encoded);
+        assert_eq!(
+            coder.try_decode_into(&encoded, &mut decoded_values, 1),
+            Err(DecodeError::RunTooLong)
+        );
+    }
+
+    #[test]
+    fn test_try_decode_overflow() {
+        // k = 31, quotient 2 -> 110 followed by 31 remainder bits is 2^32
+        let encoded = [0b1100_0000, 0, 0, 0, 0b0011_1111];
+        let coder = create_rice_coder(31);
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            coder.try_decode_into(&encoded, &mut decoded_values, 1),
+            Err(DecodeError::Overflow)
+        );
+        // Decoding without errors stops instead of wrapping
+        coder.decode_into(&encoded, &mut decoded_values, 1);
+        assert!(decoded_values.is_empty());
+
+        // Quotient 1 is the largest that fits
+        let encoded = [0b1011_1111, 0xFF, 0xFF, 0xFF, 0b1111_1111];
+        assert_eq!(
+            coder.try_decode_into(&encoded, &mut decoded_values, 1),
+            Ok(5)
+        );
+        assert_eq!(decoded_values, [u32::MAX]);
+    }
+
+    #[test]
+    fn test_lsb_first_bytes() {
+        // k = 2: 5 -> 10 01, 0 -> 0 00, padded with a `1`
+        let mut encoded: Vec<u8> = Vec::new();
+        RiceCoder::with_bit_order(2, BitOrder::LsbFirst).encode_vals(&[5, 0], &mut encoded);
+        assert_eq!(encoded, [0b1000_1001]);
+        let mut msb_first: Vec<u8> = Vec::new();
+        RiceCoder::new(2).encode_vals(&[5, 0], &mut msb_first);
+        assert_eq!(msb_first, [0b1001_0001]);
+        assert_ne!(encoded, msb_first);
+    }
 
-    // Sort the values
-    let mut sorted_values = values.to_vec();
-    sorted_values.sort_unstable();
+    proptest! {
+        #[test]
+        fn test_bit_order_roundtrip(values in prop::collection::vec(0u32..=500_000, 1..40), k in 0u8..16) {
+            let mut encoded_per_order = Vec::new();
+            for order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+                let mut coder = RiceCoder::with_bit_order(k, order);
+                prop_assert_eq!(coder.bit_order(), order);
+                let mut encoded: Vec<u8> = Vec::new();
+                coder.encode_vals(&values, &mut encoded);
 
-    // Determine the index for the desired percentile
-    let percentile_index = (percentile * sorted_values.len()) / 100;
+                let mut decoded_values = Vec::new();
+                let num_bytes = coder.decode_into(&encoded, &mut decoded_values, values.len() as u32);
+                prop_assert_eq!(num_bytes, encoded.len());
+                prop_assert_eq!(&decoded_values, &values);
+                decoded_values.clear();
+                prop_assert_eq!(coder.try_decode_into(&encoded, &mut decoded_values, u32::MAX), Ok(encoded.len()));
+                prop_assert_eq!(&decoded_values, &values);
+                prop_assert_eq!(coder.iter_decode(&encoded, u32::MAX).collect::<Vec<_>>(), values.clone());
+                encoded_per_order.push(encoded);
+            }
+            // The same bits, only packed in a different order within each byte
+            let (msb_first, lsb_first) = (&encoded_per_order[0], &encoded_per_order[1]);
+            prop_assert_eq!(msb_first.len(), lsb_first.len());
+            prop_assert!(msb_first.iter().zip(lsb_first).all(|(msb, lsb)| msb.reverse_bits() == *lsb));
+        }
+    }
 
-    // Handle case where percentile index is out of bounds
-    let percentile_index = std::cmp::min(percentile_index, sorted_values.len() - 1);
+    #[test]
+    fn test_bit_order_survives_reset() {
+        let mut coder = RiceCoder::with_bit_order(3, BitOrder::LsbFirst);
+        coder.reset_with_k(5);
+        assert_eq!(coder.bit_order(), BitOrder::LsbFirst);
+    }
 
-    // Get the value at the desired percentile
-    let value_at_percentile = sorted_values[percentile_index];
+    #[test]
+    fn test_little_endian_words() {
+        // k = 0: 3 -> 1110, 0 -> 0, 2 -> 110, padded with `1`s to a whole word
+        let mut coder = RiceCoder::with_byte_endian(0, ByteEndian::Little);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&[3, 0, 2], &mut encoded);
+        assert_eq!(encoded, [0xFF, 0xFF, 0xFF, 0b1110_0110]);
+        assert_eq!(
+            u32::from_le_bytes(encoded[..].try_into().unwrap()) >> 24,
+            0b1110_0110
+        );
+        let mut decoded_values = Vec::new();
+        assert_eq!(coder.decode_into(&encoded, &mut decoded_values, 10), 4);
+        assert_eq!(decoded_values, [3, 0, 2]);
 
-    // Use the log2 of the percentile value to estimate k
-    (32 - value_at_percentile.leading_zeros()) as u8
-}
+        // A stream ending on a word boundary gets a full word of padding
+        encoded.clear();
+        coder.encode_vals(&[31], &mut encoded);
+        assert_eq!(encoded, [0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            coder.try_decode_verified_into(&encoded, &mut decoded_values, 1),
+            Ok(8)
+        );
+        assert_eq!(
+            coder.try_decode_verified_into(&encoded[..4], &mut decoded_values, 1),
+            Err(DecodeError::BadPadding)
+        );
 
-pub struct RiceCoder {
-    k: u8,
-    buffer: u64,    // A 64-bit buffer to store bits before flushing
-    buffer_len: u8, // Number of bits currently in the buffer
-}
+        coder.reset_with_k(5);
+        assert_eq!(coder.byte_endian(), ByteEndian::Little);
+        assert_eq!(RiceCoder::new(5).byte_endian(), ByteEndian::Big);
+    }
 
-impl RiceCoder {
-    /// Constructor to create a RiceCoder with a const generic k value
-    pub fn new(k: u8) -> Self {
-        RiceCoder {
-            k,
-            buffer: 0,
-            buffer_len: 0,
+    proptest! {
+        #[test]
+        fn test_byte_endian_roundtrip(values in prop::collection::vec(0u32..=500_000, 0..40), k in 0u8..16, lsb_first in any::<bool>()) {
+            let order = if lsb_first { BitOrder::LsbFirst } else { BitOrder::MsbFirst };
+            let mut encoded_per_endian = Vec::new();
+            for endian in [ByteEndian::Big, ByteEndian::Little] {
+                let mut coder = RiceCoderBuilder::new().k(k).bit_order(order).byte_endian(endian).build();
+                prop_assert_eq!(coder.byte_endian(), endian);
+                let mut encoded: Vec<u8> = Vec::new();
+                coder.encode_vals(&values, &mut encoded);
+                // Followed by another stream
+                let mut second: Vec<u8> = Vec::new();
+                coder.encode_vals(&[7, 1000], &mut second);
+                let mut stream = encoded.clone();
+                stream.extend_from_slice(&second);
+
+                let mut decoded_values = Vec::new();
+                let num_bytes = coder.decode_into(&stream, &mut decoded_values, values.len() as u32);
+                prop_assert_eq!(num_bytes, encoded.len());
+                prop_assert_eq!(&decoded_values, &values);
+                decoded_values.clear();
+                prop_assert_eq!(coder.try_decode_verified_into(&stream, &mut decoded_values, values.len() as u32), Ok(encoded.len()));
+                prop_assert_eq!(coder.iter_decode(&encoded, u32::MAX).collect::<Vec<_>>(), values.clone());
+                decoded_values.clear();
+                coder.decode_into(&stream[num_bytes..], &mut decoded_values, 2);
+                prop_assert_eq!(decoded_values, [7, 1000]);
+
+                let mut exact: Vec<u8> = Vec::new();
+                coder.encode_vals_no_finalize(&values, &mut exact);
+                let total_bits = coder.finalize_exact(&mut exact);
+                let mut decoded_values = Vec::new();
+                prop_assert_eq!(coder.decode_bits_into(&exact, total_bits, &mut decoded_values), exact.len());
+                prop_assert_eq!(&decoded_values, &values);
+                encoded_per_endian.push(encoded);
+            }
+            // The same bytes padded to whole words, with the bytes of each word reversed
+            let (big, little) = (&encoded_per_endian[0], &encoded_per_endian[1]);
+            prop_assert_eq!(big.len() as u64, encoded_len_bytes(&values, k));
+            prop_assert_eq!(little.len() as u64, encoded_len_bytes(&values, k).next_multiple_of(4));
+            let mut padded = big.clone();
+            padded.resize(big.len().div_ceil(4) * 4, 0xFF);
+            prop_assert_eq!(little.len(), padded.len());
+            let reversed_words = padded
+                .chunks(4)
+                .zip(little.chunks(4))
+                .all(|(big_word, little_word)| big_word.iter().rev().eq(little_word));
+            prop_assert!(reversed_words);
+            if big.len() > 1 {
+                prop_assert_ne!(big, little);
+            }
         }
     }
 
-    /// Helper function to flush the buffer to the output vector once it's full or when needed
-    fn flush_buffer(&mut self, output: &mut Vec<u8>) {
-        while self.buffer_len >= 8 {
-            let byte = (self.buffer >> (self.buffer_len - 8)) as u8;
-            output.push(byte);
-            self.buffer_len -= 8;
-            self.buffer &= (1 << self.buffer_len) - 1; // Keep only remaining bits in buffer
-        }
+    #[test]
+    fn test_k_zero_is_unary() {
+        let mut coder = create_rice_coder(0);
+        assert_eq!(coder.k(), 0);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&[3, 0, 2], &mut encoded);
+        assert_eq!(encoded, [0b1110_0110, 0b1111_1111]);
+        test_rice_coding(0, &[3, 0, 2, 40, 0, 0, 1]);
     }
 
-    /// Helper function to write bits to the buffer
-    #[inline]
-    fn write_bits_to_buffer(&mut self, value: u32, num_bits: u8) {
-        self.buffer <<= num_bits;
-        self.buffer |= value as u64;
-        self.buffer_len += num_bits;
+    #[test]
+    fn test_k_31() {
+        assert_eq!(create_rice_coder(31).k(), 31);
+        test_rice_coding(31, &[0, u32::MAX, 1 << 31, (1 << 31) - 1]);
     }
 
-    pub fn encode_vals(&mut self, values: &[u32], output: &mut Vec<u8>) {
-        for value in values {
-            self.encode(*value, output);
+    #[test]
+    fn test_k_0_pure_unary() {
+        // 0 -> 0, 1 -> 10, 2 -> 110, 3 -> 1110
+        let mut coder = create_rice_coder(0);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&[0, 1, 2, 3], &mut encoded);
+        assert_eq!(encoded, [0b0101_1011, 0b1011_1111]);
+
+        let mut decoded_values = Vec::new();
+        assert_eq!(coder.decode_into(&encoded, &mut decoded_values, 4), 2);
+        assert_eq!(decoded_values, [0, 1, 2, 3]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_k_31_roundtrip(values in prop::collection::vec(prop_oneof![Just(u32::MAX >> 1), 0u32..=u32::MAX >> 1], 1..40)) {
+            let mut coder = create_rice_coder(31);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+            // Every value is a single `0` followed by 31 remainder bits
+            prop_assert_eq!(encoded.len(), (values.len() * 32 + 1).div_ceil(8));
+
+            let mut decoded_values = Vec::new();
+            prop_assert_eq!(coder.try_decode_into(&encoded, &mut decoded_values, values.len() as u32), Ok(encoded.len()));
+            prop_assert_eq!(decoded_values, values);
         }
-        self.finalize(output);
     }
 
-    /// Rice encoding for a given integer
-    /// Need to call finalize at the end
-    #[inline]
-    pub fn encode(&mut self, value: u32, output: &mut Vec<u8>) {
-        let quotient = value >> self.k; // value / 2^k
-        let remainder = value & ((1 << self.k) - 1); // value % 2^k
+    #[test]
+    #[should_panic(expected = "k must be at most 31, got 32")]
+    fn test_k_32_rejected() {
+        create_rice_coder(32);
+    }
 
-        let mut remaining = quotient;
+    #[test]
+    #[should_panic(expected = "k must be at most 31")]
+    fn test_reset_with_k_32_rejected() {
+        create_rice_coder(3).reset_with_k(32);
+    }
 
-        // Write blocks of 32 `1`s at a time
-        while remaining >= 32 {
-            self.write_bits_to_buffer(0xFFFFFFFF, 32); // 0xFFFFFFFF is thirty-two 1s
-            remaining -= 32;
-            self.flush_buffer(output);
+    #[test]
+    fn test_encode_best() {
+        for (name, values) in bench_datasets() {
+            let (k, encoded) = encode_best(&values);
+            assert_eq!(k, best_k_by_size(&values), "{}", name);
+            assert_eq!(encoded.len() as u64, 1 + encoded_len_bytes(&values, k));
+            assert_eq!(decode_best(&encoded), Ok(values), "{}", name);
         }
+    }
+
+    proptest! {
+        #[test]
+        fn test_table_decode_matches_bitwise(values in prop::collection::vec(prop_oneof![0u32..6, 0u32..=300], 0..60), k in 0u8..=2, num_values in 0u32..70) {
+            let mut coder = create_rice_coder(k);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
 
-        // Write any remaining 1s
-        if remaining > 0 {
-            let mask = (1u32 << remaining) - 1; // Create a mask of `remaining` 1s
-            self.write_bits_to_buffer(mask, remaining as u8);
+            // `decode_into` uses the table for small k, `iter_decode` decodes bit by bit
+            let mut decoded_values = Vec::new();
+            let num_bytes = coder.decode_into(&encoded, &mut decoded_values, num_values);
+            let expected: Vec<u32> = coder.iter_decode(&encoded, num_values).collect();
+            prop_assert_eq!(&decoded_values, &expected);
+            if num_values as usize >= values.len() {
+                prop_assert_eq!(num_bytes, encoded.len());
+            }
         }
+    }
 
-        // Write the final `0` after all 1s
-        self.write_bits_to_buffer(0, 1);
+    proptest! {
+        #[test]
+        fn test_decode_chunks_matches_decode_into(values in prop::collection::vec(0u32..=500_000, 0..60), k in 0u8..16, chunk_size in 1usize..20) {
+            let mut coder = create_rice_coder(k);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
 
-        // Write the remainder in binary form (k bits)
-        self.write_bits_to_buffer(remainder, self.k);
-        self.flush_buffer(output);
-    }
+            let mut chunks: Vec<Vec<u32>> = Vec::new();
+            let num_bytes = coder.decode_chunks(&encoded, chunk_size, |chunk| chunks.push(chunk.to_vec()));
+            prop_assert_eq!(num_bytes, encoded.len());
+            prop_assert!(chunks.iter().all(|chunk| !chunk.is_empty() && chunk.len() <= chunk_size));
 
-    /// Finalize encoding by flushing any remaining bits in the buffer
-    /// We will pad the remaining bits with `1`s to signal the end of the stream.
-    pub fn finalize(&mut self, output: &mut Vec<u8>) {
-        // Pad with 1s, so entry is invalid. On decompression this will be the
-        // EOF marker
-        let padding = 8 - self.buffer_len;
-        self.write_bits_to_buffer((1 << padding) - 1, padding);
-        self.flush_buffer(output);
+            let mut expected = Vec::new();
+            coder.decode_into(&encoded, &mut expected, u32::MAX);
+            prop_assert_eq!(chunks.concat(), expected);
+        }
     }
 
-    /// Rice decoding for multiple integers from a byte stream
-    ///
-    /// Returns the number of bytes read
-    pub fn decode_into(&self, input: &[u8], out: &mut Vec<u32>, num_values: u32) -> usize {
-        let mut bit_pos: u8 = 0;
-        let mut byte_pos: usize = 0;
-
-        // Helper function to read a single bit from the input buffer
-        fn read_bit(input: &[u8], byte_pos: &mut usize, bit_pos: &mut u8) -> Option<bool> {
-            if *byte_pos >= input.len() {
-                return None;
+    proptest! {
+        #[test]
+        fn test_finalize_exact_roundtrip(values in prop::collection::vec(0u32..=500_000, 0..40), k in 0u8..16) {
+            let mut coder = create_rice_coder(k);
+            let mut encoded: Vec<u8> = Vec::new();
+            for value in &values {
+                coder.encode(*value, &mut encoded);
             }
+            let total_bits = coder.finalize_exact(&mut encoded);
+            prop_assert_eq!(total_bits, encoded_len_bits(&values, k));
+            prop_assert_eq!(encoded.len() as u64, total_bits.div_ceil(8));
 
-            let bit = (input[*byte_pos] >> (7 - *bit_pos)) & 1 == 1;
-            *bit_pos = (*bit_pos + 1) % 8;
+            let mut decoded_values = Vec::new();
+            let num_bytes = coder.decode_bits_into(&encoded, total_bits, &mut decoded_values);
+            prop_assert_eq!(num_bytes, encoded.len());
+            prop_assert_eq!(&decoded_values, &values);
 
-            if *bit_pos == 0 {
-                *byte_pos += 1;
+            // One bit less drops the last value instead of completing it from the padding
+            if let Some(total_bits) = total_bits.checked_sub(1) {
+                decoded_values.clear();
+                coder.decode_bits_into(&encoded, total_bits, &mut decoded_values);
+                prop_assert_eq!(&decoded_values[..], &values[..values.len() - 1]);
             }
+        }
+    }
+
+    #[test]
+    fn test_finalize_exact_zero_padding() {
+        // k = 0: 1 -> 10, 0 -> 0, padded with `0`s instead of `1`s
+        let mut coder = create_rice_coder(0);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode(1, &mut encoded);
+        coder.encode(0, &mut encoded);
+        assert_eq!(coder.finalize_exact(&mut encoded), 3);
+        assert_eq!(encoded, [0b1000_0000]);
+
+        // The padding would decode as five `0` values without the bit count
+        let mut decoded_values = Vec::new();
+        coder.decode_bits_into(&encoded, 3, &mut decoded_values);
+        assert_eq!(decoded_values, [1, 0]);
+        // A bit count within a value doesn't complete it from the bits after the count
+        decoded_values.clear();
+        coder.decode_bits_into(&encoded, 1, &mut decoded_values);
+        assert!(decoded_values.is_empty());
 
-            Some(bit)
+        // Byte aligned streams get no padding byte, and the bit count restarts per stream
+        for value in [1, 0, 0, 0, 0, 0, 0] {
+            coder.encode(value, &mut encoded);
         }
+        assert_eq!(coder.finalize_exact(&mut encoded), 8);
+        assert_eq!(encoded, [0b1000_0000, 0b1000_0000]);
+        coder.encode(3, &mut encoded);
+        assert_eq!(coder.finalize_exact(&mut encoded), 4);
+    }
 
-        // Helper function to read multiple bits from the input buffer
-        fn read_bits(
-            input: &[u8],
-            num_bits: u8,
-            byte_pos: &mut usize,
-            bit_pos: &mut u8,
-        ) -> Option<u32> {
-            let mut value = 0;
-            for _ in 0..num_bits {
-                if let Some(bit) = read_bit(input, byte_pos, bit_pos) {
-                    value = (value << 1) | (bit as u32);
-                } else {
-                    return None; // Not enough bits
-                }
-            }
-            Some(value)
+    proptest! {
+        #[test]
+        fn test_decode_into_slice_matches_decode_into(values in prop::collection::vec(0u32..=500_000, 0..40), k in 0u8..16, capacity in 0usize..50) {
+            let mut coder = create_rice_coder(k);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+
+            let mut expected = Vec::new();
+            coder.decode_into(&encoded, &mut expected, capacity as u32);
+            let mut out = vec![u32::MAX; capacity];
+            let num_written = coder.decode_into_slice(&encoded, &mut out);
+            prop_assert_eq!(num_written, core::cmp::min(capacity, values.len()));
+            prop_assert_eq!(&out[..num_written], &expected[..]);
         }
+    }
 
-        while byte_pos < input.len() && out.len() < num_values as usize {
-            // Decode unary quotient
-            let mut quotient: u32 = 0;
-            while let Some(bit) = read_bit(input, &mut byte_pos, &mut bit_pos) {
-                if bit {
-                    quotient += 1;
-                } else {
-                    break;
-                }
+    proptest! {
+        #[test]
+        fn test_decode_pair_matches_decode_into(
+            values_a in prop::collection::vec(0u32..=500_000, 0..40),
+            values_b in prop::collection::vec(0u32..=500_000, 0..40),
+            k_a in 0u8..16,
+            k_b in 0u8..16,
+            num_vals in 0u32..50,
+        ) {
+            let mut encoded_a: Vec<u8> = Vec::new();
+            create_rice_coder(k_a).encode_vals(&values_a, &mut encoded_a);
+            let mut encoded_b: Vec<u8> = Vec::new();
+            create_rice_coder(k_b).encode_vals(&values_b, &mut encoded_b);
+
+            let (mut out_a, mut out_b) = (Vec::new(), Vec::new());
+            let num_bytes = decode_pair_into(
+                (&encoded_a, k_a),
+                (&encoded_b, k_b),
+                &mut out_a,
+                &mut out_b,
+                num_vals,
+            );
+
+            let (mut expected_a, mut expected_b) = (Vec::new(), Vec::new());
+            let expected_bytes = (
+                create_rice_coder(k_a).decode_into(&encoded_a, &mut expected_a, num_vals),
+                create_rice_coder(k_b).decode_into(&encoded_b, &mut expected_b, num_vals),
+            );
+            prop_assert_eq!(num_bytes, expected_bytes);
+            prop_assert_eq!(out_a, expected_a);
+            prop_assert_eq!(out_b, expected_b);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_decode_one_matches_decode_into(values in prop::collection::vec(0u32..=500_000, 0..40), k in 0u8..16) {
+            let mut coder = create_rice_coder(k);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+
+            let (mut byte_pos, mut bit_pos) = (0, 0);
+            let mut decoded_values = Vec::new();
+            let mut offsets = Vec::new();
+            while let Some(value) = coder.decode_one(&encoded, &mut byte_pos, &mut bit_pos) {
+                decoded_values.push(value);
+                offsets.push((byte_pos, bit_pos));
             }
+            prop_assert_eq!(&decoded_values, &values);
+            // The cursors stay at the EOF marker
+            prop_assert_eq!(byte_pos, encoded.len() - 1);
 
-            // Decode the binary remainder
-            if let Some(remainder) = read_bits(input, self.k, &mut byte_pos, &mut bit_pos) {
-                out.push((quotient << self.k) + remainder);
-            } else {
-                break; // Not enough bits to complete the number, hit EOF marker
+            // Random access via the recorded offsets
+            for (i, (mut byte_pos, mut bit_pos)) in offsets.iter().copied().enumerate() {
+                let value = coder.decode_one(&encoded, &mut byte_pos, &mut bit_pos);
+                prop_assert_eq!(value, values.get(i + 1).copied());
             }
         }
-        byte_pos + 1
     }
-}
-
-pub fn create_rice_coder(k: u8) -> RiceCoder {
-    RiceCoder::new(k)
-}
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use proptest::prelude::*;
 
     #[test]
-    fn test_case_1() {
-        let original_values: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
-        test_rice_coding(3, &original_values);
+    fn test_decode_one_out_of_bounds() {
+        let coder = create_rice_coder(3);
+        let (mut byte_pos, mut bit_pos) = (10, 7);
+        assert_eq!(coder.decode_one(&[0, 0], &mut byte_pos, &mut bit_pos), None);
+        assert_eq!((byte_pos, bit_pos), (10, 7));
+        // 0 001 starting at bit 4
+        let (mut byte_pos, mut bit_pos) = (0, 4);
+        assert_eq!(
+            coder.decode_one(&[0b1111_0001], &mut byte_pos, &mut bit_pos),
+            Some(1)
+        );
+        assert_eq!((byte_pos, bit_pos), (1, 0));
     }
 
     #[test]
-    fn test_calculate_optimal_k_small_values() {
-        let values = vec![1, 2, 3, 4, 5, 6, 7, 8];
-        let optimal_k = estimate_optimal_k(&values, 50);
-        assert_eq!(optimal_k, 3);
+    fn test_counted_blocks_concatenated() {
+        let first: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        let second: Vec<u32> = (0..200).collect();
+        let mut coder = create_rice_coder(3);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals_counted(&first, &mut encoded);
+        coder.encode_vals_counted(&[], &mut encoded);
+        coder.encode_vals_counted(&second, &mut encoded);
 
-        let optimal_k_90 = estimate_optimal_k(&values, 90);
-        assert_eq!(optimal_k_90, 4);
+        let mut decoded_values = Vec::new();
+        let mut pos = 0;
+        pos += coder
+            .decode_counted_into(&encoded[pos..], &mut decoded_values)
+            .unwrap();
+        assert_eq!(decoded_values, first);
+        decoded_values.clear();
+        pos += coder
+            .decode_counted_into(&encoded[pos..], &mut decoded_values)
+            .unwrap();
+        assert!(decoded_values.is_empty());
+        pos += coder
+            .decode_counted_into(&encoded[pos..], &mut decoded_values)
+            .unwrap();
+        assert_eq!(decoded_values, second);
+        assert_eq!(pos, encoded.len());
+
+        assert_eq!(
+            coder.decode_counted_into(&encoded[..4], &mut decoded_values),
+            Err(DecodeError::UnexpectedEof)
+        );
+        assert_eq!(
+            coder.decode_counted_into(&[], &mut decoded_values),
+            Err(DecodeError::MissingHeader)
+        );
+        let mut too_many: Vec<u8> = Vec::new();
+        write_varint(u32::MAX as u64 + 1, &mut too_many);
+        assert_eq!(
+            coder.decode_counted_into(&too_many, &mut decoded_values),
+            Err(DecodeError::Overflow)
+        );
     }
 
     #[test]
-    fn print_test() {
-        for val in 1..12 {
-            print!("{:0>2} ", val);
-            print::<2>(val);
+    fn test_concat_aligned() {
+        let groups: Vec<Vec<u32>> = vec![vec![37, 12, 5, 150], vec![], (0..200).collect(), vec![8]];
+        let mut coder = create_rice_coder(3);
+        let blocks: Vec<Vec<u8>> = groups
+            .iter()
+            .map(|values| {
+                let mut encoded: Vec<u8> = Vec::new();
+                coder.encode_vals(values, &mut encoded);
+                encoded
+            })
+            .collect();
+        let counts: Vec<u32> = groups.iter().map(|values| values.len() as u32).collect();
+        let (concat, offsets) = RiceCoder::concat_aligned(&blocks, &counts);
+
+        let mut expected: Vec<u8> = Vec::new();
+        for values in &groups {
+            coder.encode_vals_counted(values, &mut expected);
         }
-        //print(2, 2);
-        //print(2, 3);
-        //print(4, 4);
-        //print(1, 40);
-        //print(1, 41);
-        //print(1, 42);
+        assert_eq!(concat, expected);
+
+        // Sequentially
+        let mut pos = 0;
+        for (values, offset) in groups.iter().zip(&offsets) {
+            assert_eq!(pos, *offset);
+            let mut decoded_values = Vec::new();
+            pos += coder
+                .decode_counted_into(&concat[pos..], &mut decoded_values)
+                .unwrap();
+            assert_eq!(&decoded_values, values);
+        }
+        assert_eq!(pos, concat.len());
+
+        // Random access via the offsets
+        let mut decoded_values = Vec::new();
+        coder
+            .decode_counted_into(&concat[offsets[2]..], &mut decoded_values)
+            .unwrap();
+        assert_eq!(decoded_values, groups[2]);
+
+        let (concat, offsets) = RiceCoder::concat_aligned::<Vec<u8>>(&[], &[]);
+        assert!(concat.is_empty() && offsets.is_empty());
     }
 
-    fn print<const K: u8>(val: u32) {
-        let mut coder = RiceCoder::new(K); // Example with k = 3
+    #[test]
+    fn test_rle() {
+        let values: Vec<u32> = [3, 3, 3, 3, 0, 0, 0, 7, 7, 7, 7, 7, 7, 1]
+            .iter()
+            .copied()
+            .cycle()
+            .take(1000)
+            .collect();
+        let mut coder = create_rice_coder(1);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_rle(&values, &mut encoded);
+        assert_eq!(encoded[0], 1);
+        let mut plain: Vec<u8> = Vec::new();
+        coder.encode_vals(&values, &mut plain);
+        assert!(encoded.len() * 2 < plain.len());
 
-        // Encoding
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            coder.decode_rle_into(&encoded, &mut decoded_values, u32::MAX),
+            Ok(encoded.len())
+        );
+        assert_eq!(decoded_values, values);
+
+        // Without runs the values are stored plain
+        let values: Vec<u32> = (0..100).collect();
+        let mut coder = create_rice_coder(best_k_by_size(&values));
         let mut encoded: Vec<u8> = Vec::new();
-        coder.encode(val, &mut encoded);
-        coder.finalize(&mut encoded);
-        print_bits(&encoded);
+        coder.encode_rle(&values, &mut encoded);
+        assert_eq!(encoded[0], 0);
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            coder.decode_rle_into(&encoded, &mut decoded_values, u32::MAX),
+            Ok(encoded.len())
+        );
+        assert_eq!(decoded_values, values);
+
+        assert_eq!(
+            coder.decode_rle_into(&[2], &mut decoded_values, u32::MAX),
+            Err(DecodeError::InvalidMode(2))
+        );
+        assert_eq!(
+            coder.decode_rle_into(&[1, 1, 3], &mut decoded_values, u32::MAX),
+            Err(DecodeError::MissingHeader)
+        );
+        assert_eq!(
+            coder.decode_rle_into(&[1, 1, 3, 40], &mut decoded_values, u32::MAX),
+            Err(DecodeError::InvalidK(40))
+        );
     }
 
-    fn print_bits(bytes: &[u8]) {
-        for byte in bytes.iter() {
-            // Print the binary representation of each byte, padded to 8 bits
-            print!("{:08b} ", byte);
-        }
-        println!(); // Newline after printing all bytes
+    #[test]
+    fn test_rle_max_values() {
+        let values: Vec<u32> = [vec![7; 500], vec![2; 500]].concat();
+        let mut coder = create_rice_coder(2);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_rle(&values, &mut encoded);
+        assert_eq!(encoded[0], 1);
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            coder.decode_rle_into(&encoded, &mut decoded_values, 1000),
+            Ok(encoded.len())
+        );
+        assert_eq!(decoded_values, values);
+        assert_eq!(
+            coder.decode_rle_into(&encoded, &mut Vec::new(), 999),
+            Err(DecodeError::TooManyValues { max_values: 999 })
+        );
+
+        // A single run of the value 0, repeated u32::MAX + 1 times
+        let crafted = [1, 1, 0, 31, 0b0101_1111, 0xFF, 0xFF, 0xFF, 0xFF];
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            coder.decode_rle_into(&crafted, &mut decoded_values, 1_000_000),
+            Err(DecodeError::TooManyValues {
+                max_values: 1_000_000
+            })
+        );
+        assert!(decoded_values.is_empty());
+
+        // Plain streams are limited by their count
+        let values: Vec<u32> = (0..100).collect();
+        let mut coder = create_rice_coder(best_k_by_size(&values));
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_rle(&values, &mut encoded);
+        assert_eq!(encoded[0], 0);
+        assert_eq!(
+            coder.decode_rle_into(&encoded, &mut Vec::new(), 99),
+            Err(DecodeError::TooManyValues { max_values: 99 })
+        );
     }
 
-    // Property-based test for random values
     proptest! {
         #[test]
-        fn test_rice_coding_random_values(values in prop::collection::vec(0u32..=500_000, 1..20), k in 1u8..8) {
-        //fn test_rice_coding_random_values(values in prop::collection::vec(0u32..=500, 1..3), k in 1u8..8) {
-            let mut coder = create_rice_coder(k); // Create a RiceCoder with the given k value
-
-            // Encoding
+        fn test_rle_roundtrip(values in prop::collection::vec(prop_oneof![Just(0u32), Just(5), 0u32..=1000], 0..80), k in 0u8..8) {
+            let mut coder = create_rice_coder(k);
             let mut encoded: Vec<u8> = Vec::new();
-            coder.encode_vals(&values, &mut encoded);
+            coder.encode_rle(&values, &mut encoded);
+            encoded.extend_from_slice(&[0, 1, 2]);
 
-            // Decoding
             let mut decoded_values = Vec::new();
-            let num_bytes = coder.decode_into(&encoded, &mut decoded_values, values.len() as u32);
-            prop_assert_eq!(num_bytes, encoded.len());
-
-            // Assert that the decoded values match the original values
-            prop_assert_eq!(values, decoded_values);
+            let num_bytes = coder.decode_rle_into(&encoded, &mut decoded_values, u32::MAX).unwrap();
+            prop_assert_eq!(num_bytes, encoded.len() - 3);
+            prop_assert_eq!(decoded_values, values);
         }
     }
 
+    #[test]
+    fn test_reuse_coder() {
+        let first: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        let second: Vec<u32> = vec![1, 2, 3, 1000];
+
+        let mut coder = create_rice_coder(3);
+        let mut encoded_first: Vec<u8> = Vec::new();
+        coder.encode_vals(&first, &mut encoded_first);
+
+        // Abandon a stream midway
+        let mut discarded: Vec<u8> = Vec::new();
+        coder.encode(5, &mut discarded);
+        coder.reset_with_k(4);
+
+        let mut encoded_second: Vec<u8> = Vec::new();
+        coder.encode_vals(&second, &mut encoded_second);
+
+        let mut decoded_values = Vec::new();
+        create_rice_coder(3).decode_into(&encoded_first, &mut decoded_values, 7);
+        assert_eq!(decoded_values, first);
+        let mut decoded_values = Vec::new();
+        coder.decode_into(&encoded_second, &mut decoded_values, 4);
+        assert_eq!(decoded_values, second);
+    }
+
     fn test_rice_coding(k: u8, values: &[u32]) {
         let mut coder = create_rice_coder(k); // Create a RiceCoder with the given k value
 
@@ -262,6 +4226,168 @@ mod tests {
         assert_eq!(values, decoded_values);
     }
 
+    #[test]
+    fn test_encode_vals_at() {
+        let blocks: [&[u32]; 3] = [&[37, 12, 5], &[], &[150, 255, 0, 10]];
+        let mut coder = create_rice_coder(3);
+        let mut output: Vec<u8> = b"header".to_vec();
+        let ranges: Vec<Range<usize>> = blocks
+            .iter()
+            .map(|block| coder.encode_vals_at(block, &mut output))
+            .collect();
+        assert_eq!(ranges[0].start, 6);
+        assert_eq!(ranges[2].end, output.len());
+        for (block, range) in blocks.iter().zip(ranges) {
+            assert_eq!(range.len() as u64, encoded_len_bytes(block, 3));
+            let mut decoded_values = Vec::new();
+            let num_bytes =
+                coder.decode_into(&output[range.clone()], &mut decoded_values, u32::MAX);
+            assert_eq!(num_bytes, range.len());
+            assert_eq!(decoded_values, *block);
+        }
+    }
+
+    #[test]
+    fn test_encode_vals_large_input() {
+        let values: Vec<u32> = (0..1_000_000u32)
+            .map(|i| i.wrapping_mul(2_654_435_761) >> 20)
+            .collect();
+        let k = best_k_by_size(&values);
+        let mut encoded: Vec<u8> = Vec::new();
+        create_rice_coder(k).encode_vals(&values, &mut encoded);
+        assert_eq!(encoded.len(), reserve_for(&values, k));
+
+        let mut coder = create_rice_coder(k);
+        let mut encoded_per_value: Vec<u8> = Vec::new();
+        for value in &values {
+            coder.encode(*value, &mut encoded_per_value);
+        }
+        coder.finalize(&mut encoded_per_value);
+        assert_eq!(encoded, encoded_per_value);
+
+        let mut encoded_staged: Vec<u8> = Vec::new();
+        RiceCoder::with_staging(k).encode_vals(&values, &mut encoded_staged);
+        assert_eq!(encoded, encoded_staged);
+    }
+
+    #[test]
+    fn test_staging_matches_unstaged() {
+        for (name, values) in bench_datasets() {
+            let k = best_k_by_size(&values);
+            for order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+                for endian in [ByteEndian::Big, ByteEndian::Little] {
+                    let context = format!("{} {:?} {:?}", name, order, endian);
+                    let builder = RiceCoderBuilder::new()
+                        .k(k)
+                        .bit_order(order)
+                        .byte_endian(endian);
+                    let mut coder = builder.build();
+                    let mut staged = builder.staging(true).build();
+                    assert!(staged.is_staged() && !coder.is_staged());
+
+                    let (mut expected, mut encoded): (Vec<u8>, Vec<u8>) = (vec![7], vec![7]);
+                    coder.encode_vals(&values, &mut expected);
+                    staged.encode_vals(&values, &mut encoded);
+                    assert_eq!(encoded, expected, "{}", context);
+
+                    coder.encode_vals_no_finalize(&values, &mut expected);
+                    let total_bits = coder.finalize_exact(&mut expected);
+                    staged.encode_vals_no_finalize(&values, &mut encoded);
+                    assert_eq!(staged.finalize_exact(&mut encoded), total_bits);
+                    assert_eq!(encoded, expected, "{} finalize_exact", context);
+                }
+            }
+        }
+
+        // The output lags behind until finalize
+        let mut coder = RiceCoder::with_staging(0);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals_no_finalize(&[40, 40], &mut encoded);
+        assert!(encoded.is_empty());
+        coder.finalize(&mut encoded);
+        assert_eq!(encoded.len(), 11);
+        coder.encode_vals_no_finalize(&[40, 40], &mut encoded);
+        coder.reset();
+        assert!(coder.is_staged());
+        assert_eq!(coder, RiceCoder::with_staging(0));
+    }
+
+    #[test]
+    fn test_encode_vals_consuming() {
+        for (name, values) in bench_datasets() {
+            let k = best_k_by_size(&values);
+            let mut expected: Vec<u8> = Vec::new();
+            create_rice_coder(k).encode_vals(&values, &mut expected);
+            let encoded = create_rice_coder(k).encode_vals_consuming(values.clone());
+            assert_eq!(encoded, expected, "{}", name);
+            let mut decoded_values = Vec::new();
+            create_rice_coder(k).decode_into(&encoded, &mut decoded_values, u32::MAX);
+            assert_eq!(decoded_values, values, "{}", name);
+        }
+        assert_eq!(
+            create_rice_coder(3).encode_vals_consuming(Vec::new()),
+            [0xFF]
+        );
+
+        // Escape codes make the output shorter than the plain Rice codes
+        let values = vec![3, 1_000_000, 2];
+        let encoded = RiceCoder::with_escape(2, 4).encode_vals_consuming(values.clone());
+        let mut decoded_values = Vec::new();
+        RiceCoder::with_escape(2, 4).decode_into(&encoded, &mut decoded_values, 3);
+        assert_eq!(decoded_values, values);
+    }
+
+    #[test]
+    fn test_remainder_boundaries() {
+        for k in 0..=8u8 {
+            let block = 1u32 << k;
+            // All-ones remainder just below a quotient step, the step and the next block's end
+            let values = [block - 1, block, block + 1, 2 * block - 1];
+            test_rice_coding(k, &values);
+            let mut encoded: Vec<u8> = Vec::new();
+            create_rice_coder(k).encode_vals(&values, &mut encoded);
+            assert_eq!(encoded, reference_encode(&values, k), "k = {}", k);
+            for value in values {
+                test_rice_coding(k, &[value]);
+                test_rice_coding(k, &[value; 9]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_num_values() {
+        let values: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        let mut coder = create_rice_coder(3);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&values, &mut encoded);
+
+        let mut decoded_values = vec![1];
+        coder.decode_into(&encoded, &mut decoded_values, 3);
+        assert_eq!(decoded_values, [1, 37, 12, 5]);
+
+        let mut decoded_values = Vec::new();
+        let num_bytes = coder.decode_all_into(&encoded, &mut decoded_values);
+        assert_eq!(num_bytes, encoded.len());
+        assert_eq!(decoded_values, values);
+    }
+
+    #[test]
+    fn test_padding_never_decodes_as_value() {
+        // With k = 0 the `1`s padding looks like a unary run without remainder
+        for k in 0..4 {
+            for values in [&[7][..], &[2], &[0; 8], &[1, 5], &[3, 0, 0, 0, 0]] {
+                let mut coder = create_rice_coder(k);
+                let mut encoded: Vec<u8> = Vec::new();
+                coder.encode_vals(values, &mut encoded);
+
+                let mut decoded_values = Vec::new();
+                let num_bytes = coder.decode_all_into(&encoded, &mut decoded_values);
+                assert_eq!(num_bytes, encoded.len());
+                assert_eq!(decoded_values, values, "k={}", k);
+            }
+        }
+    }
+
     #[test]
     fn test_regression_1() {
         test_rice_coding(3, &[96]);
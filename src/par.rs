@@ -0,0 +1,45 @@
+use alloc::vec::Vec;
+
+use rayon::prelude::*;
+
+use crate::{encoded_len_bytes, RiceCoder};
+
+/// Encodes independent blocks in parallel, each with its own `RiceCoder` for `k`.
+///
+/// The output for each block is identical to `RiceCoder::encode_vals`.
+///
+/// # Panics
+///
+/// Panics if `k > 31`.
+pub fn encode_blocks_par(blocks: &[&[u32]], k: u8) -> Vec<Vec<u8>> {
+    let coder = RiceCoder::new(k);
+    blocks
+        .par_iter()
+        .map(|block| {
+            let mut coder = coder;
+            let mut output = Vec::with_capacity(encoded_len_bytes(block, k) as usize);
+            coder.encode_vals(block, &mut output);
+            output
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn test_encode_blocks_par_matches_serial(blocks in prop::collection::vec(prop::collection::vec(0u32..=500_000, 0..40), 0..20), k in 0u8..16) {
+            let block_refs: Vec<&[u32]> = blocks.iter().map(|block| block.as_slice()).collect();
+            let encoded = encode_blocks_par(&block_refs, k);
+            prop_assert_eq!(encoded.len(), blocks.len());
+            for (block, encoded) in blocks.iter().zip(&encoded) {
+                let mut expected: Vec<u8> = Vec::new();
+                RiceCoder::new(k).encode_vals(block, &mut expected);
+                prop_assert_eq!(encoded, &expected);
+            }
+        }
+    }
+}
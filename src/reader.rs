@@ -0,0 +1,159 @@
+use std::io::{self, BufRead};
+
+use crate::bits::BitReader;
+use crate::{DecodeError, RiceCoder};
+
+impl RiceCoder {
+    /// Decodes up to `num_vals` values from `reader`, e.g. a file, reading its buffers one at a
+    /// time instead of loading the whole stream. Counterpart of `RiceWriter`.
+    ///
+    /// Values may span the buffers of the reader. Once `num_vals` values are decoded, the reader
+    /// is left after the bytes `decode_into` reports as read, so the stream can be followed by
+    /// other data.
+    ///
+    /// Fails with `io::ErrorKind::UnexpectedEof` if the input ends in the middle of a value and
+    /// with `io::ErrorKind::InvalidData` if a value doesn't fit into a `u32`. Ending before
+    /// `num_vals` values is not an error if only the `1`s padding written by `finalize` is left.
+    pub fn decode_from_reader<R: BufRead>(
+        &self,
+        mut reader: R,
+        num_vals: u32,
+    ) -> io::Result<Vec<u32>> {
+        let mut out = Vec::new();
+        // Unread bytes of the previous buffers, starting with the byte the next value starts in
+        let mut pending: Vec<u8> = Vec::new();
+        let mut bit_pos = 0;
+        while out.len() < num_vals as usize {
+            let buf = reader.fill_buf()?;
+            let at_end = buf.is_empty();
+            let buf_len = buf.len();
+            let pending_len = pending.len();
+            pending.extend_from_slice(buf);
+
+            let mut bits = BitReader::with_position(&pending, 0, bit_pos, self.bit_order())
+                .endian(self.byte_endian());
+            while out.len() < num_vals as usize {
+                let start = bits;
+                match self.try_decode_value(&mut bits) {
+                    Ok(value) => out.push(value),
+                    Err(DecodeError::UnexpectedEof) if !at_end || start.at_padding() => {
+                        bits = start; // Incomplete value, or only the padding is left
+                        break;
+                    }
+                    Err(DecodeError::UnexpectedEof) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            DecodeError::UnexpectedEof,
+                        ));
+                    }
+                    Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+                }
+            }
+            if at_end {
+                break;
+            }
+            // Like `decode_into`, the bytes read end with the byte, or word for
+            // `ByteEndian::Little`, holding the padding after the last value
+            let (byte_pos, next_bit_pos) = (bits.byte_pos(), bits.bit_pos());
+            let padding_end = bits.padding_end();
+            if out.len() == num_vals as usize && padding_end <= pending.len() {
+                // The last value ends after the previous buffers, since it didn't fit into them
+                reader.consume(padding_end - pending_len);
+                break;
+            }
+            reader.consume(buf_len);
+            if out.len() == num_vals as usize {
+                // The rest of the padding is in the next buffers
+                let mut remaining = padding_end - pending.len();
+                while remaining > 0 {
+                    let available = reader.fill_buf()?.len();
+                    if available == 0 {
+                        break;
+                    }
+                    let num_bytes = core::cmp::min(remaining, available);
+                    reader.consume(num_bytes);
+                    remaining -= num_bytes;
+                }
+                break;
+            }
+            // Keep whole words, so the bytes of a little-endian word stay together
+            let num_drained = byte_pos / 4 * 4;
+            pending.drain(..num_drained);
+            bit_pos = ((byte_pos - num_drained) * 8) as u8 + next_bit_pos;
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor, Read};
+
+    use super::*;
+    use crate::{create_rice_coder, ByteEndian};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_decode_from_cursor() {
+        let values: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        let mut coder = create_rice_coder(3);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&values, &mut encoded);
+
+        let decoded_values = coder
+            .decode_from_reader(Cursor::new(&encoded), 100)
+            .unwrap();
+        assert_eq!(decoded_values, values);
+
+        // Followed by other data, which stays in the reader
+        encoded.extend_from_slice(b"rest");
+        let mut cursor = Cursor::new(&encoded);
+        let decoded_values = coder.decode_from_reader(&mut cursor, 7).unwrap();
+        assert_eq!(decoded_values, values);
+        let mut rest = String::new();
+        cursor.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "rest");
+    }
+
+    #[test]
+    fn test_decode_from_reader_errors() {
+        // k = 3: quotient 7, cut before the remainder
+        let coder = create_rice_coder(3);
+        let err = coder
+            .decode_from_reader(Cursor::new([0b1111_1110]), 1)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let coder = create_rice_coder(31);
+        let err = coder
+            .decode_from_reader(Cursor::new([0b1100_0000, 0, 0, 0, 0b0011_1111]), 1)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    proptest! {
+        #[test]
+        fn test_decode_from_small_buffers(values in prop::collection::vec(0u32..=5_000, 0..40), k in 0u8..8, capacity in 1usize..6, num_vals in 0u32..50, little in any::<bool>()) {
+            let endian = if little { ByteEndian::Little } else { ByteEndian::Big };
+            let mut coder = RiceCoder::with_byte_endian(k, endian);
+            let mut encoded: Vec<u8> = Vec::new();
+            coder.encode_vals(&values, &mut encoded);
+            let mut expected = Vec::new();
+            let num_bytes = coder.decode_into(&encoded, &mut expected, num_vals);
+
+            // Values span the buffers of a few bytes
+            let followed = num_vals > 0 && num_vals as usize <= values.len();
+            if followed {
+                encoded.push(42);
+            }
+            let mut reader = BufReader::with_capacity(capacity, Cursor::new(&encoded));
+            let decoded_values = coder.decode_from_reader(&mut reader, num_vals).unwrap();
+            prop_assert_eq!(&decoded_values, &expected);
+            if followed {
+                let mut rest = Vec::new();
+                reader.read_to_end(&mut rest).unwrap();
+                prop_assert_eq!(rest.len(), encoded.len() - num_bytes);
+            }
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use alloc::vec::Vec;
+
+use crate::bits::{BitBuffer, BitReader};
+use crate::DecodeError;
+
+/// Rice coder for 64-bit values, with `k` in `0..=63`.
+///
+/// Uses the same stream format as `RiceCoder`, so for values fitting in 32 bits and `k <= 31`
+/// the output is identical.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RiceCoder64 {
+    k: u8,
+    bits: BitBuffer,
+}
+
+impl RiceCoder64 {
+    pub fn new(k: u8) -> Self {
+        assert!(k <= 63, "k must be at most 63, got {}", k);
+        RiceCoder64 {
+            k,
+            bits: BitBuffer::new(),
+        }
+    }
+
+    pub fn encode_vals(&mut self, values: &[u64], output: &mut Vec<u8>) {
+        for value in values {
+            self.encode(*value, output);
+        }
+        self.finalize(output);
+    }
+
+    /// Rice encoding for a given integer
+    /// Need to call finalize at the end
+    #[inline]
+    pub fn encode(&mut self, value: u64, output: &mut Vec<u8>) {
+        let mut quotient = value >> self.k;
+        let remainder = value & ((1u64 << self.k) - 1);
+
+        while quotient > u32::MAX as u64 {
+            self.bits.write_ones(u32::MAX, output);
+            quotient -= u32::MAX as u64;
+        }
+        self.bits.write_ones(quotient as u32, output);
+        self.bits.write_bits(0, 1);
+
+        // The bit buffer takes at most 32 bits per write
+        if self.k > 32 {
+            self.bits.write_bits((remainder >> 32) as u32, self.k - 32);
+            self.bits.flush(output);
+            self.bits.write_bits(remainder as u32, 32);
+        } else {
+            self.bits.write_bits(remainder as u32, self.k);
+        }
+        self.bits.flush(output);
+    }
+
+    /// Finalize encoding by flushing any remaining bits in the buffer
+    /// We will pad the remaining bits with `1`s to signal the end of the stream.
+    pub fn finalize(&mut self, output: &mut Vec<u8>) {
+        self.bits.finalize(output);
+    }
+
+    /// Rice decoding for `num_values` integers from a byte stream
+    ///
+    /// Returns the number of bytes read
+    pub fn decode_into(&self, input: &[u8], out: &mut Vec<u64>, num_values: u32) -> usize {
+        let end_len = out.len().saturating_add(num_values as usize);
+        let mut reader = BitReader::new(input);
+
+        while out.len() < end_len {
+            match self.decode_value(&mut reader) {
+                Some(value) => out.push(value),
+                None => break, // Not enough bits to complete the number, hit EOF marker
+            }
+        }
+        core::cmp::min(reader.byte_pos() + 1, input.len())
+    }
+
+    /// Like `decode_into`, but returns `DecodeError::UnexpectedEof` if the input ends in the middle
+    /// of a value, e.g. because it was truncated, and `DecodeError::Overflow` if a value doesn't fit
+    /// into a `u64`, e.g. because the input is corrupt.
+    ///
+    /// Ending before `num_values` values is not an error if only the `1`s padding written by
+    /// `finalize` is left.
+    ///
+    /// Returns the number of bytes read
+    pub fn try_decode_into(
+        &self,
+        input: &[u8],
+        out: &mut Vec<u64>,
+        num_values: u32,
+    ) -> Result<usize, DecodeError> {
+        let end_len = out.len().saturating_add(num_values as usize);
+        let mut reader = BitReader::new(input);
+        while out.len() < end_len {
+            let start = reader;
+            match self.try_decode_value(&mut reader) {
+                Ok(value) => out.push(value),
+                Err(DecodeError::UnexpectedEof) if start.at_padding() => {
+                    reader = start;
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(reader.bytes_read())
+    }
+
+    /// Decodes a single value, returning `None` if the input ends before the value is complete,
+    /// or the value doesn't fit into a `u64`.
+    #[inline]
+    fn decode_value(&self, reader: &mut BitReader) -> Option<u64> {
+        self.try_decode_value(reader).ok()
+    }
+
+    /// Like `decode_value`, but tells apart `DecodeError::UnexpectedEof` and
+    /// `DecodeError::Overflow`.
+    #[inline]
+    fn try_decode_value(&self, reader: &mut BitReader) -> Result<u64, DecodeError> {
+        // Longest quotient whose shift by `k` keeps all of its bits
+        let max_quotient = u64::MAX >> self.k;
+        let start = *reader;
+        let mut quotient: u64 = 0;
+        loop {
+            // Stop one past `max_quotient`, instead of reading the rest of a corrupt run
+            let limit =
+                core::cmp::min((max_quotient - quotient).saturating_add(1), u32::MAX as u64);
+            let run = reader
+                .read_unary(limit as u32)
+                .ok_or(DecodeError::UnexpectedEof)?;
+            quotient += run as u64;
+            if quotient > max_quotient {
+                return Err(if start.at_padding() {
+                    DecodeError::UnexpectedEof
+                } else {
+                    DecodeError::Overflow
+                });
+            }
+            if run < u32::MAX {
+                break;
+            }
+        }
+
+        let remainder = if self.k > 32 {
+            let high = reader
+                .read_bits(self.k - 32)
+                .ok_or(DecodeError::UnexpectedEof)? as u64;
+            (high << 32) | reader.read_bits(32).ok_or(DecodeError::UnexpectedEof)? as u64
+        } else {
+            reader.read_bits(self.k).ok_or(DecodeError::UnexpectedEof)? as u64
+        };
+        Ok((quotient << self.k) | remainder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_rice_coder;
+    use proptest::prelude::*;
+
+    fn test_rice_coding_64(k: u8, values: &[u64]) {
+        let mut coder = RiceCoder64::new(k);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(values, &mut encoded);
+
+        let mut decoded_values = Vec::new();
+        let num_bytes = coder.decode_into(&encoded, &mut decoded_values, values.len() as u32);
+        assert_eq!(num_bytes, encoded.len());
+        assert_eq!(decoded_values, values);
+
+        decoded_values.clear();
+        assert_eq!(
+            coder.try_decode_into(&encoded, &mut decoded_values, u32::MAX),
+            Ok(encoded.len())
+        );
+        assert_eq!(decoded_values, values);
+    }
+
+    #[test]
+    fn test_rice_64_extremes() {
+        for k in 52..=63 {
+            test_rice_coding_64(k, &[0, u64::MAX, 1, u64::MAX - 1, 1 << 63, u32::MAX as u64]);
+        }
+    }
+
+    #[test]
+    fn test_rice_64_matches_32_bit_format() {
+        let values: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        let mut encoded: Vec<u8> = Vec::new();
+        create_rice_coder(3).encode_vals(&values, &mut encoded);
+
+        let mut encoded_64: Vec<u8> = Vec::new();
+        let values_64: Vec<u64> = values.iter().map(|v| *v as u64).collect();
+        RiceCoder64::new(3).encode_vals(&values_64, &mut encoded_64);
+        assert_eq!(encoded, encoded_64);
+    }
+
+    #[test]
+    fn test_rice_64_overflow() {
+        // k = 62: the quotient 4 would shift out of a u64
+        let mut input = vec![0b1111_0000];
+        input.extend_from_slice(&[0; 8]);
+        let coder = RiceCoder64::new(62);
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            coder.try_decode_into(&input, &mut decoded_values, 1),
+            Err(DecodeError::Overflow)
+        );
+        coder.decode_into(&input, &mut decoded_values, 1);
+        assert!(decoded_values.is_empty());
+
+        // The largest quotient for k = 62 still decodes
+        let mut encoded: Vec<u8> = Vec::new();
+        RiceCoder64::new(62).encode_vals(&[u64::MAX, 5], &mut encoded);
+        assert_eq!(
+            coder.try_decode_into(&encoded, &mut decoded_values, 10),
+            Ok(encoded.len())
+        );
+        assert_eq!(decoded_values, [u64::MAX, 5]);
+        assert_eq!(
+            coder.try_decode_into(&encoded[..3], &mut decoded_values, 1),
+            Err(DecodeError::UnexpectedEof)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn test_rice_64_random_values(values in prop::collection::vec(any::<u64>(), 1..20), k in 50u8..=63) {
+            test_rice_coding_64(k, &values);
+        }
+
+        #[test]
+        fn test_rice_64_random_small_values(values in prop::collection::vec(0u64..=500_000, 1..20), k in 1u8..8) {
+            test_rice_coding_64(k, &values);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Adaptive blocks with boundaries picked from the data instead of a fixed block size.
+
+use alloc::vec::Vec;
+
+use crate::varint::{read_varint, write_varint};
+use crate::{best_k_by_size, DecodeError, RiceCoder, MAX_K};
+
+/// Fewest values per block, so the block header and padding don't outweigh a better `k`
+const MIN_BLOCK_LEN: usize = 32;
+/// Number of values the running mean of the recent bit lengths roughly spans
+const RECENT_WINDOW: f64 = 8.0;
+/// Difference in bits between the recent and the block mean bit length that starts a new block
+const SHIFT_THRESHOLD: f64 = 1.5;
+
+/// Encodes the values in blocks of varying length, each with its own `k` picked by
+/// `best_k_by_size`. Decode with `decode_auto_blocks`.
+///
+/// A new block starts when the distribution shifts: the exponential moving average of the bit
+/// lengths of the recent values departs from the mean bit length of the current block by more
+/// than 1.5 bits. Blocks hold at least 32 values, except the last one.
+///
+/// Layout: varint number of blocks, then per block its number of values as varint and its `k`
+/// as a byte, followed by the finalized Rice codes of each block.
+pub fn auto_block_encode(values: &[u32], output: &mut Vec<u8>) {
+    let block_lens = split_at_shifts(values);
+    write_varint(block_lens.len() as u64, output);
+    let mut start = 0;
+    let mut ks = Vec::with_capacity(block_lens.len());
+    for &len in &block_lens {
+        let k = best_k_by_size(&values[start..start + len]);
+        write_varint(len as u64, output);
+        output.push(k);
+        ks.push(k);
+        start += len;
+    }
+    let mut start = 0;
+    for (&len, &k) in block_lens.iter().zip(&ks) {
+        RiceCoder::new(k).encode_vals(&values[start..start + len], output);
+        start += len;
+    }
+}
+
+/// Returns the lengths of the blocks `auto_block_encode` splits `values` into
+fn split_at_shifts(values: &[u32]) -> Vec<usize> {
+    let mut block_lens = Vec::new();
+    let mut start = 0;
+    let mut block_sum = 0.0;
+    let mut recent_mean = 0.0;
+    for (i, value) in values.iter().enumerate() {
+        let num_bits = (32 - value.leading_zeros()) as f64;
+        let len = i - start;
+        if len == 0 {
+            recent_mean = num_bits;
+        } else {
+            recent_mean += (num_bits - recent_mean) / RECENT_WINDOW;
+            let shift = recent_mean - block_sum / len as f64;
+            if len >= MIN_BLOCK_LEN && !(-SHIFT_THRESHOLD..=SHIFT_THRESHOLD).contains(&shift) {
+                block_lens.push(len);
+                start = i;
+                block_sum = 0.0;
+                recent_mean = num_bits;
+            }
+        }
+        block_sum += num_bits;
+    }
+    if start < values.len() {
+        block_lens.push(values.len() - start);
+    }
+    block_lens
+}
+
+/// Decodes a stream written by `auto_block_encode`, appending the values to `out`.
+///
+/// Returns `DecodeError::MissingHeader` if the input ends within the header,
+/// `DecodeError::InvalidK` for a `k` above 31 and `DecodeError::UnexpectedEof` if a block ends
+/// before all of its values are decoded.
+///
+/// Returns the number of bytes read
+pub fn decode_auto_blocks(input: &[u8], out: &mut Vec<u32>) -> Result<usize, DecodeError> {
+    let mut pos = 0;
+    let num_blocks = read_varint(input, &mut pos).ok_or(DecodeError::MissingHeader)?;
+    // Every block takes at least two header bytes, which bounds the allocation
+    let mut blocks = Vec::with_capacity(core::cmp::min(num_blocks, input.len() as u64) as usize);
+    for _ in 0..num_blocks {
+        let len = read_varint(input, &mut pos).ok_or(DecodeError::MissingHeader)?;
+        let k = *input.get(pos).ok_or(DecodeError::MissingHeader)?;
+        if k > MAX_K {
+            return Err(DecodeError::InvalidK(k));
+        }
+        pos += 1;
+        blocks.push((len, k));
+    }
+    for (len, k) in blocks {
+        let start_len = out.len();
+        let num_values = u32::try_from(len).unwrap_or(u32::MAX);
+        pos += RiceCoder::new(k).decode_into(&input[pos..], out, num_values);
+        if ((out.len() - start_len) as u64) < len {
+            return Err(DecodeError::UnexpectedEof);
+        }
+    }
+    Ok(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoded_len_bytes;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_auto_blocks_follow_shifts() {
+        // Small values, large ones and small ones again
+        let values: Vec<u32> = (0..300)
+            .map(|i| {
+                if (100..200).contains(&i) {
+                    50_000 + i
+                } else {
+                    i % 4
+                }
+            })
+            .collect();
+        let block_lens = split_at_shifts(&values);
+        assert_eq!(block_lens.iter().sum::<usize>(), values.len());
+        assert_eq!(block_lens.len(), 3, "{:?}", block_lens);
+        // The moving average lags a few values behind the shift
+        assert!((100..104).contains(&block_lens[0]), "{:?}", block_lens);
+
+        let mut encoded: Vec<u8> = Vec::new();
+        auto_block_encode(&values, &mut encoded);
+        let single_k = encoded_len_bytes(&values, best_k_by_size(&values));
+        assert!((encoded.len() as u64) < single_k);
+        let mut fixed_blocks: Vec<u8> = Vec::new();
+        RiceCoder::new(0).encode_adaptive(&values, 64, &mut fixed_blocks);
+        assert!(encoded.len() < fixed_blocks.len());
+
+        let mut decoded_values = vec![7];
+        let num_bytes = decode_auto_blocks(&encoded, &mut decoded_values).unwrap();
+        assert_eq!(num_bytes, encoded.len());
+        assert_eq!(decoded_values[1..], values);
+    }
+
+    #[test]
+    fn test_auto_blocks_errors() {
+        let mut out = Vec::new();
+        assert_eq!(
+            decode_auto_blocks(&[], &mut out),
+            Err(DecodeError::MissingHeader)
+        );
+        assert_eq!(
+            decode_auto_blocks(&[2, 4, 0], &mut out),
+            Err(DecodeError::MissingHeader)
+        );
+        assert_eq!(
+            decode_auto_blocks(&[1, 4, 32, 0xFF], &mut out),
+            Err(DecodeError::InvalidK(32))
+        );
+        // Four values announced, the block ends after one
+        assert_eq!(
+            decode_auto_blocks(&[1, 4, 0, 0b0111_1111], &mut out),
+            Err(DecodeError::UnexpectedEof)
+        );
+
+        out.clear();
+        let mut encoded: Vec<u8> = Vec::new();
+        auto_block_encode(&[], &mut encoded);
+        assert_eq!(encoded, [0]);
+        assert_eq!(decode_auto_blocks(&encoded, &mut out), Ok(1));
+        assert!(out.is_empty());
+    }
+
+    proptest! {
+        #[test]
+        fn test_auto_blocks_roundtrip(values in prop::collection::vec(prop_oneof![0u32..16, 0u32..100_000, any::<u32>()], 0..200)) {
+            let mut encoded: Vec<u8> = Vec::new();
+            auto_block_encode(&values, &mut encoded);
+
+            let mut decoded_values = Vec::new();
+            prop_assert_eq!(decode_auto_blocks(&encoded, &mut decoded_values), Ok(encoded.len()));
+            prop_assert_eq!(decoded_values, values);
+        }
+    }
+}
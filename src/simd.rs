@@ -0,0 +1,101 @@
+//! Batched quotient/remainder extraction for `RiceCoder::encode_vals`.
+//!
+//! Uses AVX2 (detected at runtime, requires `std`) on x86_64 and NEON on aarch64, with a scalar
+//! fallback on other targets.
+
+/// Number of values split per batch
+pub(crate) const BATCH_SIZE: usize = 8;
+
+/// Splits a batch of values into quotients `value >> k` and remainders `value & (2^k - 1)`
+#[inline]
+pub(crate) fn split_batch(
+    values: &[u32; BATCH_SIZE],
+    k: u8,
+) -> ([u32; BATCH_SIZE], [u32; BATCH_SIZE]) {
+    #[cfg(all(target_arch = "x86_64", feature = "std"))]
+    {
+        if std::is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 support was checked above
+            return unsafe { split_batch_avx2(values, k) };
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        // SAFETY: NEON is always available on aarch64
+        return unsafe { split_batch_neon(values, k) };
+    }
+    #[allow(unreachable_code)]
+    split_batch_scalar(values, k)
+}
+
+#[inline]
+fn split_batch_scalar(values: &[u32; BATCH_SIZE], k: u8) -> ([u32; BATCH_SIZE], [u32; BATCH_SIZE]) {
+    let mask = (1u32 << k) - 1;
+    let mut quotients = [0u32; BATCH_SIZE];
+    let mut remainders = [0u32; BATCH_SIZE];
+    for i in 0..BATCH_SIZE {
+        quotients[i] = values[i] >> k;
+        remainders[i] = values[i] & mask;
+    }
+    (quotients, remainders)
+}
+
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+#[target_feature(enable = "avx2")]
+unsafe fn split_batch_avx2(
+    values: &[u32; BATCH_SIZE],
+    k: u8,
+) -> ([u32; BATCH_SIZE], [u32; BATCH_SIZE]) {
+    use core::arch::x86_64::*;
+
+    let mut quotients = [0u32; BATCH_SIZE];
+    let mut remainders = [0u32; BATCH_SIZE];
+    let input = _mm256_loadu_si256(values.as_ptr() as *const __m256i);
+    let shift = _mm_cvtsi32_si128(k as i32);
+    let mask = _mm256_set1_epi32(((1u32 << k) - 1) as i32);
+    let quotient = _mm256_srl_epi32(input, shift);
+    let remainder = _mm256_and_si256(input, mask);
+    _mm256_storeu_si256(quotients.as_mut_ptr() as *mut __m256i, quotient);
+    _mm256_storeu_si256(remainders.as_mut_ptr() as *mut __m256i, remainder);
+    (quotients, remainders)
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn split_batch_neon(
+    values: &[u32; BATCH_SIZE],
+    k: u8,
+) -> ([u32; BATCH_SIZE], [u32; BATCH_SIZE]) {
+    use core::arch::aarch64::*;
+
+    let mut quotients = [0u32; BATCH_SIZE];
+    let mut remainders = [0u32; BATCH_SIZE];
+    // A negative shift count shifts right
+    let shift = vdupq_n_s32(-(k as i32));
+    let mask = vdupq_n_u32((1u32 << k) - 1);
+    for half in 0..2 {
+        let input = vld1q_u32(values.as_ptr().add(half * 4));
+        vst1q_u32(
+            quotients.as_mut_ptr().add(half * 4),
+            vshlq_u32(input, shift),
+        );
+        vst1q_u32(
+            remainders.as_mut_ptr().add(half * 4),
+            vandq_u32(input, mask),
+        );
+    }
+    (quotients, remainders)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn test_split_batch_matches_scalar(values in any::<[u32; BATCH_SIZE]>(), k in 0u8..32) {
+            prop_assert_eq!(split_batch(&values, k), split_batch_scalar(&values, k));
+        }
+    }
+}
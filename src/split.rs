@@ -0,0 +1,215 @@
+//! Split encoding: the unary quotients and the fixed width remainders in separate planes.
+//!
+//! With all remainders `k` bits apart, the remainder of value `i` starts at bit `i * k` of its
+//! plane, so the remainders are unpacked independently of each other instead of after decoding
+//! each quotient.
+
+use alloc::vec::Vec;
+
+use crate::bits::{BitBuffer, BitReader};
+use crate::{DecodeError, RiceCoder};
+
+/// Where the planes of a block written by `RiceCoder::encode_split` are, relative to the start
+/// of the block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitLayout {
+    /// Number of encoded values
+    pub num_values: u32,
+    /// Start of the remainder plane, the quotient plane starts at 0
+    pub remainder_offset: usize,
+    /// Total length of the block
+    pub len: usize,
+}
+
+impl RiceCoder {
+    /// Encodes the values with the quotients and remainders in separate planes, so decoding can
+    /// unpack the remainders independently. Decode with `decode_split_into`.
+    ///
+    /// The quotient plane holds the unary codes, padded with `1`s like `finalize`, followed by the
+    /// remainder plane with `k` bits per value, padded with `0`s. Both planes are MSB-first
+    /// regardless of the bit order of the coder, and escape codes are not used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than `u32::MAX` values.
+    pub fn encode_split(&mut self, values: &[u32], output: &mut Vec<u8>) -> SplitLayout {
+        let num_values = u32::try_from(values.len()).expect("more than u32::MAX values");
+        let start = output.len();
+        let mut bits = BitBuffer::new();
+        for value in values {
+            bits.write_ones(value >> self.k, output);
+            bits.write_bits(0, 1);
+            bits.flush(output);
+        }
+        bits.finalize(output);
+
+        let remainder_offset = output.len() - start;
+        let mask = (1 << self.k) - 1;
+        for value in values {
+            bits.write_bits(value & mask, self.k);
+            bits.flush(output);
+        }
+        bits.finalize_exact(output);
+        SplitLayout {
+            num_values,
+            remainder_offset,
+            len: output.len() - start,
+        }
+    }
+
+    /// Decodes a block written by `encode_split` with the returned `layout`, appending the values
+    /// to `out`.
+    ///
+    /// Returns `DecodeError::UnexpectedEof` if the input or a plane is too short and
+    /// `DecodeError::Overflow` if a value doesn't fit into a `u32`, or `DecodeError::RunTooLong`
+    /// if a quotient exceeds the `max_quotient` of the coder. On error, `out` is left as it was.
+    ///
+    /// Returns the number of bytes read, i.e. `layout.len`
+    pub fn decode_split_into(
+        &self,
+        input: &[u8],
+        layout: &SplitLayout,
+        out: &mut Vec<u32>,
+    ) -> Result<usize, DecodeError> {
+        let block = input.get(..layout.len).ok_or(DecodeError::UnexpectedEof)?;
+        let quotient_plane = block
+            .get(..layout.remainder_offset)
+            .ok_or(DecodeError::UnexpectedEof)?;
+        let remainder_plane = &block[layout.remainder_offset..];
+        let num_values = layout.num_values as usize;
+        if ((remainder_plane.len() as u64) * 8) < num_values as u64 * self.k as u64 {
+            return Err(DecodeError::UnexpectedEof);
+        }
+
+        // Quotients first, combined with the remainders in place
+        let start_len = out.len();
+        let max_quotient = u32::MAX >> self.k;
+        let quotient_limit = self.quotient_limit(max_quotient);
+        let mut reader = BitReader::new(quotient_plane);
+        let mut read_quotient = || {
+            let quotient = reader
+                .read_unary(quotient_limit.saturating_add(1))
+                .ok_or(DecodeError::UnexpectedEof)?;
+            if quotient > max_quotient {
+                return Err(DecodeError::Overflow);
+            }
+            if quotient > quotient_limit {
+                return Err(DecodeError::RunTooLong);
+            }
+            Ok(quotient)
+        };
+        for _ in 0..num_values {
+            match read_quotient() {
+                Ok(quotient) => out.push(quotient),
+                Err(err) => {
+                    // Don't leave the raw quotients behind as if they were values
+                    out.truncate(start_len);
+                    return Err(err);
+                }
+            }
+        }
+        for (i, value) in out[start_len..].iter_mut().enumerate() {
+            *value =
+                (*value << self.k) | unpack_bits(remainder_plane, i as u64 * self.k as u64, self.k);
+        }
+        Ok(layout.len)
+    }
+}
+
+/// Reads the `num_bits` bits starting at bit `bit_pos` of an MSB-first plane, which has to
+/// contain them
+#[inline]
+fn unpack_bits(plane: &[u8], bit_pos: u64, num_bits: u8) -> u32 {
+    if num_bits == 0 {
+        return 0;
+    }
+    let byte_pos = (bit_pos / 8) as usize;
+    let word = match plane.get(byte_pos..byte_pos + 8) {
+        Some(bytes) => u64::from_be_bytes(bytes.try_into().unwrap()),
+        None => {
+            // Near the end of the plane
+            let mut bytes = [0u8; 8];
+            let available = &plane[byte_pos..];
+            bytes[..available.len()].copy_from_slice(available);
+            u64::from_be_bytes(bytes)
+        }
+    };
+    ((word << (bit_pos % 8)) >> (64 - num_bits as u32)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_rice_coder;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_split_layout() {
+        // k = 2: quotients 0, 2, 1 -> 0 110 10, remainders 3, 1, 0 -> 11 01 00
+        let mut coder = create_rice_coder(2);
+        let mut encoded: Vec<u8> = vec![42];
+        let layout = coder.encode_split(&[3, 9, 4], &mut encoded);
+        assert_eq!(encoded, [42, 0b0110_1011, 0b1101_0000]);
+        assert_eq!(
+            layout,
+            SplitLayout {
+                num_values: 3,
+                remainder_offset: 1,
+                len: 2
+            }
+        );
+
+        let mut decoded_values = Vec::new();
+        assert_eq!(
+            coder.decode_split_into(&encoded[1..], &layout, &mut decoded_values),
+            Ok(2)
+        );
+        assert_eq!(decoded_values, [3, 9, 4]);
+        assert_eq!(
+            coder.decode_split_into(&encoded[1..2], &layout, &mut decoded_values),
+            Err(DecodeError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn test_split_corrupt_quotients_leave_out_unchanged() {
+        let values: Vec<u32> = (0..100).map(|i| i * 7 % 40).collect();
+        let mut coder = create_rice_coder(3);
+        let mut encoded: Vec<u8> = Vec::new();
+        let layout = coder.encode_split(&values, &mut encoded);
+
+        // The run of `1`s at the end of the quotient plane never terminates
+        let mut corrupt = encoded.clone();
+        corrupt[layout.remainder_offset - 2..layout.remainder_offset].fill(0xFF);
+        let mut decoded_values = vec![42];
+        assert_eq!(
+            coder.decode_split_into(&corrupt, &layout, &mut decoded_values),
+            Err(DecodeError::UnexpectedEof)
+        );
+        assert_eq!(decoded_values, [42]);
+
+        let bounded = RiceCoder::with_max_quotient(3, 4);
+        assert_eq!(
+            bounded.decode_split_into(&corrupt, &layout, &mut decoded_values),
+            Err(DecodeError::RunTooLong)
+        );
+        assert_eq!(decoded_values, [42]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_split_roundtrip(values in prop::collection::vec(prop_oneof![0u32..=1000, any::<u32>()], 0..60), k in 0u8..=31) {
+            // Quotients of at most 64, so small `k` don't produce long unary runs
+            let max_value = core::cmp::min((1u64 << k) * 64, u32::MAX as u64) as u32;
+            let values: Vec<u32> = values.iter().map(|value| core::cmp::min(*value, max_value)).collect();
+            let mut coder = create_rice_coder(k);
+            let mut encoded: Vec<u8> = Vec::new();
+            let layout = coder.encode_split(&values, &mut encoded);
+            prop_assert_eq!(layout.len, encoded.len());
+
+            let mut decoded_values = Vec::new();
+            prop_assert_eq!(coder.decode_split_into(&encoded, &layout, &mut decoded_values), Ok(encoded.len()));
+            prop_assert_eq!(decoded_values, values);
+        }
+    }
+}
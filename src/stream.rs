@@ -0,0 +1,202 @@
+use alloc::vec::Vec;
+
+use crate::bits::BitReader;
+use crate::{assert_valid_k, DecodeError, RiceCoder};
+
+/// Number of bits of the per-block `k`
+const K_BITS: u8 = 5;
+
+/// Writes many blocks, each with its own `k`, into a single bit stream.
+///
+/// Unlike calling `RiceCoder::encode_vals` per block, blocks are not byte aligned, so there is no
+/// padding between them and the stream is finalized once in `finish`. Each block starts with its
+/// `k` in 5 bits and its number of values as order-0 exp-Golomb code, which takes a single bit
+/// for an empty block and 3 bits for up to 2 values.
+///
+/// Decode with `RiceStreamDecoder`.
+#[derive(Debug, Clone)]
+pub struct RiceStreamBuilder {
+    coder: RiceCoder,
+    output: Vec<u8>,
+}
+
+impl Default for RiceStreamBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RiceStreamBuilder {
+    pub fn new() -> Self {
+        RiceStreamBuilder {
+            coder: RiceCoder::new(0),
+            output: Vec::new(),
+        }
+    }
+
+    /// Appends a block of `values` coded with `k`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k > 31` or there are more than `u32::MAX` values.
+    pub fn append_block(&mut self, values: &[u32], k: u8) {
+        assert_valid_k(k);
+        let num_vals = u32::try_from(values.len()).expect("more than u32::MAX values");
+
+        let bits = &mut self.coder.bits;
+        bits.write_bits(k as u32, K_BITS);
+        // Order-0 exp-Golomb code of the count
+        let w = num_vals as u64 + 1;
+        let n = 63 - w.leading_zeros() as u8;
+        bits.write_ones(n as u32, &mut self.output);
+        bits.write_bits(0, 1);
+        bits.write_bits((w - (1u64 << n)) as u32, n);
+        bits.flush(&mut self.output);
+
+        self.coder.k = k;
+        for value in values {
+            self.coder.encode(*value, &mut self.output);
+        }
+    }
+
+    /// Finalizes the stream and returns the encoded bytes.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.coder.finalize(&mut self.output);
+        self.output
+    }
+}
+
+/// Decodes the blocks of a stream written by `RiceStreamBuilder`, one block at a time.
+#[derive(Debug, Clone)]
+pub struct RiceStreamDecoder<'a> {
+    reader: BitReader<'a>,
+}
+
+impl<'a> RiceStreamDecoder<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        RiceStreamDecoder {
+            reader: BitReader::new(input),
+        }
+    }
+
+    /// Decodes the next block into `out` and returns its number of values, or `None` at the end
+    /// of the stream.
+    ///
+    /// Returns `DecodeError::UnexpectedEof` if the input ends in the middle of a block and
+    /// `DecodeError::Overflow` if a value doesn't fit into a `u32`. On error `out` may contain a
+    /// partial block.
+    pub fn next_block(&mut self, out: &mut Vec<u32>) -> Result<Option<usize>, DecodeError> {
+        // A block header always contains a `0`, so only the final padding consists of `1`s
+        if self.reader.at_padding() {
+            return Ok(None);
+        }
+        let reader = &mut self.reader;
+        let k = reader.read_bits(K_BITS).ok_or(DecodeError::UnexpectedEof)? as u8;
+        // Groups beyond 32 can't be produced by a u32 count
+        let n = reader
+            .read_unary(33)
+            .filter(|n| *n <= 32)
+            .ok_or(DecodeError::UnexpectedEof)? as u8;
+        let suffix = reader.read_bits(n).ok_or(DecodeError::UnexpectedEof)?;
+        let num_vals = ((1u64 << n) + suffix as u64 - 1) as usize;
+
+        let coder = RiceCoder::new(k);
+        for _ in 0..num_vals {
+            out.push(coder.try_decode_value(reader)?);
+        }
+        Ok(Some(num_vals))
+    }
+
+    /// Decodes all remaining blocks
+    pub fn decode_all(&mut self) -> Result<Vec<Vec<u32>>, DecodeError> {
+        let mut blocks = Vec::new();
+        loop {
+            let mut block = Vec::new();
+            match self.next_block(&mut block)? {
+                Some(_) => blocks.push(block),
+                None => return Ok(blocks),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{best_k_by_size, encoded_len_bytes};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_stream_layout() {
+        let mut builder = RiceStreamBuilder::new();
+        // k = 1 -> 00001, count 2 -> 10 1, 1 -> 0 1, 2 -> 10 0
+        builder.append_block(&[1, 2], 1);
+        // k = 0 -> 00000, count 0 -> 0
+        builder.append_block(&[], 0);
+        let encoded = builder.finish();
+        assert_eq!(encoded, [0b0000_1101, 0b0110_0000, 0b0001_1111]);
+
+        let mut decoder = RiceStreamDecoder::new(&encoded);
+        assert_eq!(decoder.decode_all(), Ok(vec![vec![1, 2], vec![]]));
+    }
+
+    #[test]
+    fn test_stream_empty() {
+        let encoded = RiceStreamBuilder::new().finish();
+        assert_eq!(encoded, [0xFF]);
+        assert_eq!(RiceStreamDecoder::new(&encoded).decode_all(), Ok(vec![]));
+        assert_eq!(RiceStreamDecoder::new(&[]).decode_all(), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_stream_smaller_than_separate_blocks() {
+        let blocks: Vec<Vec<u32>> = (0..1000).map(|i| vec![i % 7, i % 3, i % 5]).collect();
+        let mut builder = RiceStreamBuilder::new();
+        let mut separate_len = 0;
+        for block in &blocks {
+            let k = best_k_by_size(block);
+            builder.append_block(block, k);
+            // One byte for k and one for the count, plus the byte aligned block
+            separate_len += 2 + encoded_len_bytes(block, k) as usize;
+        }
+        let encoded = builder.finish();
+        assert!(encoded.len() * 3 < separate_len * 2);
+        assert_eq!(RiceStreamDecoder::new(&encoded).decode_all(), Ok(blocks));
+    }
+
+    #[test]
+    fn test_stream_truncated() {
+        let mut builder = RiceStreamBuilder::new();
+        builder.append_block(&[100, 200, 300], 2);
+        builder.append_block(&[1, 2, 3], 1);
+        let encoded = builder.finish();
+        for len in 1..encoded.len() - 1 {
+            assert_eq!(
+                RiceStreamDecoder::new(&encoded[..len]).decode_all(),
+                Err(DecodeError::UnexpectedEof),
+                "len {}",
+                len
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_stream_roundtrip(blocks in prop::collection::vec((prop::collection::vec(0u32..=100_000, 0..20), 0u8..=31), 0..20)) {
+            let mut builder = RiceStreamBuilder::new();
+            for (values, k) in &blocks {
+                builder.append_block(values, *k);
+            }
+            let encoded = builder.finish();
+
+            let mut decoder = RiceStreamDecoder::new(&encoded);
+            let mut out = Vec::new();
+            for (values, _) in &blocks {
+                out.clear();
+                prop_assert_eq!(decoder.next_block(&mut out), Ok(Some(values.len())));
+                prop_assert_eq!(&out, values);
+            }
+            prop_assert_eq!(decoder.next_block(&mut out), Ok(None));
+        }
+    }
+}
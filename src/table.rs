@@ -0,0 +1,122 @@
+//! Table-driven decoding for small `k`.
+//!
+//! For `k <= TABLE_MAX_K` a value takes few bits, so a byte often contains several complete
+//! values. The tables map the next 8 bits of the stream to the complete values they contain,
+//! so they can be decoded in one step. The tables are built at compile time.
+
+use crate::bits::BitReader;
+
+/// Largest `k` with a decoding table
+pub(crate) const TABLE_MAX_K: u8 = 2;
+
+#[derive(Debug, Clone, Copy)]
+struct TableEntry {
+    /// Number of complete values in the byte
+    num_values: u8,
+    /// The decoded values
+    values: [u8; 8],
+    /// Bit offset after each value
+    ends: [u8; 8],
+}
+
+static TABLES: [[TableEntry; 256]; TABLE_MAX_K as usize + 1] =
+    [build_table(0), build_table(1), build_table(2)];
+
+const fn build_table(k: u32) -> [TableEntry; 256] {
+    let empty = TableEntry {
+        num_values: 0,
+        values: [0; 8],
+        ends: [0; 8],
+    };
+    let mut table = [empty; 256];
+    let mut byte: u32 = 0;
+    while byte < 256 {
+        let mut entry = empty;
+        let mut pos: u32 = 0;
+        loop {
+            // Unary quotient
+            let mut quotient = 0;
+            while pos < 8 && (byte >> (7 - pos)) & 1 == 1 {
+                quotient += 1;
+                pos += 1;
+            }
+            // The terminating `0` and the remainder need to be in the byte
+            if pos + 1 + k > 8 {
+                break;
+            }
+            pos += 1 + k;
+            let remainder = (byte >> (8 - pos)) & ((1 << k) - 1);
+            let count = entry.num_values as usize;
+            entry.values[count] = ((quotient << k) | remainder) as u8;
+            entry.ends[count] = pos as u8;
+            entry.num_values += 1;
+            if pos == 8 {
+                break;
+            }
+        }
+        table[byte as usize] = entry;
+        byte += 1;
+    }
+    table
+}
+
+/// Decodes up to `num_values` values with the table for `k`, passing them to `sink`, until less
+/// than 8 bits are left. `decode_value` handles values not completely contained in the next byte.
+#[inline]
+pub(crate) fn decode_with_table(
+    k: u8,
+    reader: &mut BitReader,
+    mut num_values: usize,
+    decode_value: impl Fn(&mut BitReader) -> Option<u32>,
+    mut sink: impl FnMut(u32),
+) {
+    let table = &TABLES[k as usize];
+    while num_values > 0 {
+        let Some(byte) = reader.peek_byte() else {
+            break;
+        };
+        let entry = &table[byte as usize];
+        if entry.num_values == 0 {
+            // Value spans more than 8 bits
+            match decode_value(reader) {
+                Some(value) => sink(value),
+                None => return,
+            }
+            num_values -= 1;
+            continue;
+        }
+        let num_entry_values = core::cmp::min(entry.num_values as usize, num_values);
+        for value in &entry.values[..num_entry_values] {
+            sink(*value as u32);
+        }
+        reader.advance(entry.ends[num_entry_values - 1] as u32);
+        num_values -= num_entry_values;
+    }
+    // Less than a byte left
+    while num_values > 0 {
+        match decode_value(reader) {
+            Some(value) => sink(value),
+            None => break,
+        }
+        num_values -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_entries() {
+        // k = 1: 0|1 10|0 0|0 1... -> 1, 2, 0 and an incomplete value
+        let entry = &TABLES[1][0b0110_0001];
+        assert_eq!(entry.num_values, 3);
+        assert_eq!(entry.values[..3], [1, 2, 0]);
+        assert_eq!(entry.ends[..3], [2, 5, 7]);
+
+        // k = 0: eight zeros
+        assert_eq!(TABLES[0][0].num_values, 8);
+        // Only `1`s: no complete value
+        assert_eq!(TABLES[2][0xFF].num_values, 0);
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::{Duration, Instant};
+
+use crate::RiceCoder;
+
+/// Decode throughput measured by `RiceCoder::decode_bench`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodeTiming {
+    /// Number of times the stream was decoded
+    pub iterations: u32,
+    /// Total time of all iterations
+    pub elapsed: Duration,
+    /// Values decoded per iteration
+    pub num_values: usize,
+    /// Bytes read per iteration
+    pub num_bytes: usize,
+}
+
+impl DecodeTiming {
+    /// Decoded values per second, infinite if the measurement was too short to register
+    pub fn values_per_sec(&self) -> f64 {
+        self.per_sec(self.num_values)
+    }
+
+    /// Bytes of input decoded per second, infinite if the measurement was too short to register
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.per_sec(self.num_bytes)
+    }
+
+    fn per_sec(&self, per_iteration: usize) -> f64 {
+        per_iteration as f64 * self.iterations as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl RiceCoder {
+    /// Decodes `num_vals` values of `input` `iterations` times and measures the throughput, for
+    /// a quick check without a benchmark harness.
+    ///
+    /// The output buffer is allocated once and cleared between iterations, so the measurement
+    /// covers only the decode loop.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `iterations == 0`.
+    pub fn decode_bench(&self, input: &[u8], num_vals: u32, iterations: u32) -> DecodeTiming {
+        assert!(iterations > 0, "iterations must be positive");
+        let mut out = Vec::with_capacity(core::cmp::min(num_vals as usize, input.len() * 8));
+        let mut num_bytes = 0;
+        let start = Instant::now();
+        for _ in 0..iterations {
+            out.clear();
+            num_bytes = self.decode_into(std::hint::black_box(input), &mut out, num_vals);
+            std::hint::black_box(&out);
+        }
+        DecodeTiming {
+            iterations,
+            elapsed: start.elapsed(),
+            num_values: out.len(),
+            num_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_rice_coder;
+
+    #[test]
+    fn test_decode_bench() {
+        let values: Vec<u32> = (0..1000).map(|i| i % 37).collect();
+        let mut coder = create_rice_coder(4);
+        let mut encoded: Vec<u8> = Vec::new();
+        coder.encode_vals(&values, &mut encoded);
+
+        let timing = coder.decode_bench(&encoded, 1000, 5);
+        assert_eq!(timing.iterations, 5);
+        assert_eq!(timing.num_values, 1000);
+        assert_eq!(timing.num_bytes, encoded.len());
+        assert!(timing.values_per_sec() > 0.0);
+        assert!(timing.bytes_per_sec() > 0.0);
+    }
+
+    #[test]
+    fn test_decode_timing_rates() {
+        let timing = DecodeTiming {
+            iterations: 4,
+            elapsed: Duration::from_millis(500),
+            num_values: 100,
+            num_bytes: 50,
+        };
+        assert_eq!(timing.values_per_sec(), 800.0);
+        assert_eq!(timing.bytes_per_sec(), 400.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "iterations must be positive")]
+    fn test_decode_bench_zero_iterations() {
+        create_rice_coder(1).decode_bench(&[0xFF], 1, 0);
+    }
+}
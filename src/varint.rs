@@ -0,0 +1,67 @@
+use alloc::vec::Vec;
+
+/// Writes `value` as LEB128 varint: 7 bits per byte, least significant group first, with the
+/// high bit set on all bytes but the last.
+pub(crate) fn write_varint(mut value: u64, output: &mut Vec<u8>) {
+    while value >= 0x80 {
+        output.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+/// Number of bytes `write_varint` writes for `value`
+pub(crate) fn varint_len(value: u64) -> usize {
+    let num_bits = 64 - value.leading_zeros() as usize;
+    core::cmp::max(1, num_bits.div_ceil(7))
+}
+
+/// Reads a LEB128 varint written by `write_varint` and advances `pos`.
+///
+/// Returns `None` if the input ends mid varint or the value exceeds 64 bits.
+pub(crate) fn read_varint(input: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value: u64 = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *input.get(*pos)?;
+        *pos += 1;
+        if shift == 63 && byte > 1 {
+            return None;
+        }
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+        if shift > 63 {
+            return None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_varint_bytes() {
+        let mut output = Vec::new();
+        write_varint(300, &mut output);
+        assert_eq!(output, [0b1010_1100, 0b0000_0010]);
+        assert_eq!(read_varint(&output, &mut 0), Some(300));
+        assert_eq!(read_varint(&output[..1], &mut 0), None);
+    }
+
+    proptest! {
+        #[test]
+        fn test_varint_roundtrip(value in any::<u64>()) {
+            let mut output = Vec::new();
+            write_varint(value, &mut output);
+            let mut pos = 0;
+            prop_assert_eq!(read_varint(&output, &mut pos), Some(value));
+            prop_assert_eq!(pos, output.len());
+            prop_assert_eq!(varint_len(value), output.len());
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use alloc::vec::Vec;
+
+use crate::{DecodeError, RiceCoder, HEADER_VERSION};
+
+/// A borrowed stream written by `RiceCoder::encode_with_header`, with the header already
+/// validated, so the values can be decoded later without copying the bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RiceView<'a> {
+    k: u8,
+    data: &'a [u8],
+}
+
+impl<'a> RiceView<'a> {
+    /// The `k` stored in the header
+    pub fn k(&self) -> u8 {
+        self.k
+    }
+
+    /// The Rice codes after the header
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Decodes all values of the stream
+    pub fn decode(self) -> Vec<u32> {
+        let mut out = Vec::new();
+        RiceCoder::new(self.k).decode_all_into(self.data, &mut out);
+        out
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for RiceView<'a> {
+    type Error = DecodeError;
+
+    /// Reads the header byte, returning `DecodeError::MissingHeader` for an empty input and
+    /// `DecodeError::UnsupportedVersion` for another format version
+    fn try_from(input: &'a [u8]) -> Result<Self, DecodeError> {
+        let (&header, data) = input.split_first().ok_or(DecodeError::MissingHeader)?;
+        let version = header >> 5;
+        if version != HEADER_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        Ok(RiceView {
+            k: header & 0b1_1111,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_rice_coder;
+
+    #[test]
+    fn test_view_from_header_stream() {
+        let values: Vec<u32> = vec![37, 12, 5, 150, 255, 0, 10];
+        let mut encoded: Vec<u8> = Vec::new();
+        create_rice_coder(5).encode_with_header(&values, &mut encoded);
+
+        let view = RiceView::try_from(&encoded[..]).unwrap();
+        assert_eq!(view.k(), 5);
+        assert_eq!(view.data(), &encoded[1..]);
+        assert_eq!(view.decode(), values);
+    }
+
+    #[test]
+    fn test_view_errors() {
+        assert_eq!(RiceView::try_from(&[][..]), Err(DecodeError::MissingHeader));
+        assert_eq!(
+            RiceView::try_from(&[(7 << 5) | 3, 0][..]),
+            Err(DecodeError::UnsupportedVersion(7))
+        );
+        // A header without values is a valid empty stream
+        let view = RiceView::try_from(&[HEADER_VERSION << 5][..]).unwrap();
+        assert!(view.decode().is_empty());
+    }
+}
@@ -0,0 +1,65 @@
+use std::io::{self, Write};
+
+use crate::RiceCoder;
+
+/// Streaming Rice encoder, writing completed bytes directly to the underlying writer.
+///
+/// The output is identical to `RiceCoder::encode_vals` followed by writing the `Vec`.
+pub struct RiceWriter<W: Write> {
+    coder: RiceCoder,
+    writer: W,
+    scratch: Vec<u8>, // Completed bytes of the current value, reused between pushes
+}
+
+impl<W: Write> RiceWriter<W> {
+    pub fn new(writer: W, coder: RiceCoder) -> Self {
+        RiceWriter {
+            coder,
+            writer,
+            scratch: Vec::with_capacity(16),
+        }
+    }
+
+    /// Encodes a value and writes all completed bytes to the underlying writer
+    pub fn push(&mut self, value: u32) -> io::Result<()> {
+        self.coder.encode(value, &mut self.scratch);
+        self.write_scratch()
+    }
+
+    /// Pads the last byte, writes it and returns the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.coder.finalize(&mut self.scratch);
+        self.write_scratch()?;
+        Ok(self.writer)
+    }
+
+    fn write_scratch(&mut self) -> io::Result<()> {
+        if !self.scratch.is_empty() {
+            self.writer.write_all(&self.scratch)?;
+            self.scratch.clear();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_rice_coder;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn test_rice_writer_matches_encode_vals(values in prop::collection::vec(0u32..=500_000, 0..20), k in 1u8..8) {
+            let mut writer = RiceWriter::new(Vec::new(), create_rice_coder(k));
+            for value in &values {
+                writer.push(*value).unwrap();
+            }
+            let streamed = writer.finish().unwrap();
+
+            let mut encoded: Vec<u8> = Vec::new();
+            create_rice_coder(k).encode_vals(&values, &mut encoded);
+            prop_assert_eq!(streamed, encoded);
+        }
+    }
+}